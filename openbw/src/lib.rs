@@ -9,5 +9,4 @@ pub mod errors {
 }
 pub use errors::*;
 
-pub mod third_party;
 pub mod ui;
@@ -6,7 +6,7 @@ use starcraft_assets;
 use std::io::Cursor;
 use std::sync::Arc;
 
-use openbw::{self, third_party};
+use openbw;
 
 use image;
 use luminance;
@@ -57,8 +57,8 @@ fn main() {
     let map = starcraft_assets::map::Map::from_mpq_file("(2)Destination.scx").unwrap();
 
     let unified_mpq_archive = starcraft_assets::mpq::UnifiedMPQArchive::from_existing(vec![
-        third_party::mpq::ReadonlyArchive::open("StarDat.mpq").unwrap(),
-        third_party::mpq::ReadonlyArchive::open("BrooDat.mpq").unwrap(),
+        starcraft_assets::mpq::MPQArchive::map("StarDat.mpq").unwrap(),
+        starcraft_assets::mpq::MPQArchive::map("BrooDat.mpq").unwrap(),
     ]);
 
     let asset_loader = starcraft_assets::loader::AssetLoader::new(
@@ -67,6 +67,19 @@ fn main() {
     );
     let assets = starcraft_assets::Assets::from(Arc::new(asset_loader)).unwrap();
 
+    let tables = starcraft_assets::dat::Tables::load(&unified_mpq_archive).unwrap();
+    let images_tbl = starcraft_assets::tbl::TBL::from_buffer(&mut Cursor::new(
+        &unified_mpq_archive.read_file("arr\\images.tbl").unwrap(),
+    ))
+    .unwrap();
+    let graphics = openbw::ui::DatSpriteGraphics::load(
+        &map.overlays(&assets.cv5s),
+        &tables,
+        &images_tbl,
+        &unified_mpq_archive,
+    )
+    .unwrap();
+
     println!("Overall: Thing took {}ms", sw.elapsed_ms());
 
     let mut surface = luminance_glfw::GlfwSurface::new(
@@ -90,7 +103,7 @@ fn main() {
 
     tex.upload_raw(
         GenMipmaps::Yes,
-        openbw::ui::generate_bitmap(&map.dimensions, &map.mega_tile_ids, &assets)
+        openbw::ui::generate_map_bitmap(&map, &assets, &graphics)
             .map(|bitmap| {
                 image::ImageBuffer::from_fn(
                     (map.dimensions.width * 32) as u32,
@@ -1,28 +1,65 @@
 use super::errors::*;
 use starcraft_assets;
-use std::mem::MaybeUninit;
+use starcraft_assets::dat::images::UnitGraphics;
+use starcraft_assets::map::Overlay;
+use std::collections::HashMap;
+
+/// A rendered image of palette indices and the palette they refer to.
+///
+/// Palette effects such as remapping can be applied before resolving it to colors.
+#[derive(Debug, Clone)]
+pub struct IndexedBitmap {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+    pub palette: starcraft_assets::wpe::WPEs,
+}
+
+impl IndexedBitmap {
+    /// Resolves every pixel to its palette color
+    pub fn to_rgb(&self) -> Vec<[u8; 3]> {
+        use rayon::prelude::*;
+        self.pixels
+            .par_iter()
+            .map(|color_index| self.palette[*color_index as usize].0)
+            .collect::<Vec<_>>()
+    }
+
+    /// Replaces every palette index with its entry in `table`
+    pub fn remap(&mut self, table: &[u8; 256]) {
+        for color_index in self.pixels.iter_mut() {
+            *color_index = table[*color_index as usize];
+        }
+    }
+}
 
 pub fn generate_bitmap(
     dimensions: &starcraft_assets::chk::Dimensions,
     megatiles: &Vec<starcraft_assets::chk::MegaTileID>,
     assets: &starcraft_assets::Assets,
 ) -> Result<Vec<[u8; 3]>> {
+    generate_indexed_bitmap(dimensions, megatiles, assets).map(|bitmap| bitmap.to_rgb())
+}
+
+pub fn generate_indexed_bitmap(
+    dimensions: &starcraft_assets::chk::Dimensions,
+    megatiles: &Vec<starcraft_assets::chk::MegaTileID>,
+    assets: &starcraft_assets::Assets,
+) -> Result<IndexedBitmap> {
     let width = dimensions.width * 32;
     let height = dimensions.height * 32;
     let size = width * height;
-    let mut out: Vec<[u8; 3]> = Vec::with_capacity(size);
-    out.resize(size, unsafe { MaybeUninit::uninit().assume_init() });
 
     use rayon::prelude::*;
-    Ok(out
+    let pixels = (0..size)
         .into_par_iter()
-        .enumerate()
-        .map(|(i, _)| {
+        .map(|i| {
             let x = i % width / 32;
             let y = i / width / 32;
 
             let megatile = &megatiles[x + y * dimensions.width];
-            let megatile_id = assets.cv5s[megatile.group_index()][megatile.subtile_index()];
+            let megatile_id =
+                assets.cv5s[megatile.group_index()][megatile.subtile_index()] as usize;
 
             let x2 = i % width % 32 / 8;
             let y2 = i / width % 32 / 8;
@@ -31,14 +68,324 @@ pub fn generate_bitmap(
 
             let x3 = i % width % 32 % 8;
             let y3 = i / width % 32 % 8;
-            let color = if minitile.is_horizontally_flipped() {
-                &assets.wpes[wpe_ref[(7 - x3) + y3 * 8]]
+            if minitile.is_horizontally_flipped() {
+                wpe_ref[(7 - x3) + y3 * 8]
             } else {
-                &assets.wpes[wpe_ref[x3 + y3 * 8]]
-            };
+                wpe_ref[x3 + y3 * 8]
+            }
+        })
+        .collect::<Vec<_>>();
+
+    Ok(IndexedBitmap {
+        width,
+        height,
+        pixels,
+        palette: assets.wpes.clone(),
+    })
+}
+
+/// A palette-indexed frame of a sprite. Palette index 0 is transparent.
+#[derive(Debug, Clone)]
+pub struct SpriteFrame {
+    /// Width of the sprite the frame belongs to; sprites are centered on their position
+    pub sprite_width: usize,
+    /// Height of the sprite the frame belongs to
+    pub sprite_height: usize,
+    /// Horizontal position of the frame within the sprite
+    pub x_offset: usize,
+    /// Vertical position of the frame within the sprite
+    pub y_offset: usize,
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl SpriteFrame {
+    /// Frame `index` of `grp`, mirrored for sprites facing west if `flipped`
+    pub fn from_grp(
+        grp: &starcraft_assets::grp::GRP,
+        index: usize,
+        flipped: bool,
+    ) -> Option<SpriteFrame> {
+        let frame = grp.frames.get(index)?;
+        let frame = if flipped {
+            frame.flipped(grp.width)
+        } else {
+            frame.clone()
+        };
 
-            color
+        Some(SpriteFrame {
+            sprite_width: grp.width,
+            sprite_height: grp.height,
+            x_offset: frame.x_offset,
+            y_offset: frame.y_offset,
+            width: frame.width,
+            height: frame.height,
+            pixels: frame.pixels,
         })
-        .map(|x| x.0)
-        .collect::<Vec<_>>())
+    }
+}
+
+/// Resolves sprites drawn over a map to the frame they are drawn with.
+pub trait SpriteGraphics: Sync {
+    fn frame(&self, overlay: &Overlay) -> Option<&SpriteFrame>;
+}
+
+/// The first frame of each sprite a map draws, followed through the DAT tables to its GRP,
+/// such as `unit\thingy\tileset\jungle\*.grp` for doodad overlays.
+#[derive(Debug, Clone, Default)]
+pub struct DatSpriteGraphics {
+    frames: HashMap<(bool, u16, bool), SpriteFrame>,
+}
+
+impl DatSpriteGraphics {
+    /// Loads the GRPs of `overlays` from `fs`. Overlays whose ids are outside of the
+    /// tables are not drawn.
+    pub fn load(
+        overlays: &[Overlay],
+        tables: &starcraft_assets::dat::Tables,
+        images_tbl: &starcraft_assets::tbl::TBL,
+        fs: &dyn starcraft_assets::fs::ReadOnlyFileSystem,
+    ) -> Result<DatSpriteGraphics> {
+        let mut grps = HashMap::new();
+        let mut frames = HashMap::new();
+        for overlay in overlays {
+            let key = DatSpriteGraphics::key(overlay);
+            if frames.contains_key(&key) {
+                continue;
+            }
+
+            let id = overlay.sprite.id as usize;
+            let graphics = if overlay.sprite.is_pure_sprite() {
+                UnitGraphics::resolve_sprite(id, &tables.sprites, &tables.images, images_tbl)
+            } else {
+                UnitGraphics::resolve(
+                    id,
+                    &tables.units,
+                    &tables.flingies,
+                    &tables.sprites,
+                    &tables.images,
+                    images_tbl,
+                )
+            };
+            let graphics = match graphics {
+                Some(graphics) => graphics,
+                None => continue,
+            };
+
+            if !grps.contains_key(&graphics.grp_path) {
+                let grp = graphics
+                    .load_grp(fs)
+                    .chain_err(|| format!("failed to load sprite {}", id))?;
+                grps.insert(graphics.grp_path.clone(), grp);
+            }
+            if let Some(frame) =
+                SpriteFrame::from_grp(&grps[&graphics.grp_path], 0, overlay.flipped)
+            {
+                frames.insert(key, frame);
+            }
+        }
+
+        Ok(DatSpriteGraphics { frames })
+    }
+
+    fn key(overlay: &Overlay) -> (bool, u16, bool) {
+        (
+            overlay.sprite.is_pure_sprite(),
+            overlay.sprite.id,
+            overlay.flipped,
+        )
+    }
+}
+
+impl SpriteGraphics for DatSpriteGraphics {
+    fn frame(&self, overlay: &Overlay) -> Option<&SpriteFrame> {
+        self.frames.get(&DatSpriteGraphics::key(overlay))
+    }
+}
+
+/// Renders the terrain of `map` with its placed sprites and doodad overlays on top.
+pub fn generate_map_bitmap(
+    map: &starcraft_assets::map::Map,
+    assets: &starcraft_assets::Assets,
+    graphics: &dyn SpriteGraphics,
+) -> Result<Vec<[u8; 3]>> {
+    generate_indexed_map_bitmap(map, assets, graphics).map(|bitmap| bitmap.to_rgb())
+}
+
+pub fn generate_indexed_map_bitmap(
+    map: &starcraft_assets::map::Map,
+    assets: &starcraft_assets::Assets,
+    graphics: &dyn SpriteGraphics,
+) -> Result<IndexedBitmap> {
+    let mut bitmap = generate_indexed_bitmap(&map.dimensions, &map.mega_tile_ids, assets)?;
+    composite_sprites(&mut bitmap, &map.overlays(&assets.cv5s), graphics);
+
+    Ok(bitmap)
+}
+
+/// Draws `overlays` over a rendered map, back to front.
+pub fn composite_sprites(
+    bitmap: &mut IndexedBitmap,
+    overlays: &[Overlay],
+    graphics: &dyn SpriteGraphics,
+) {
+    let width = bitmap.width as isize;
+    let height = bitmap.height as isize;
+
+    let mut overlays = overlays
+        .iter()
+        .filter(|overlay| !overlay.sprite.is_disabled())
+        .collect::<Vec<_>>();
+    overlays.sort_by_key(|overlay| overlay.sprite.y);
+
+    for overlay in overlays {
+        let sprite = &overlay.sprite;
+        let frame = match graphics.frame(overlay) {
+            Some(frame) => frame,
+            None => continue,
+        };
+
+        let left = sprite.x as isize - (frame.sprite_width / 2) as isize + frame.x_offset as isize;
+        let top = sprite.y as isize - (frame.sprite_height / 2) as isize + frame.y_offset as isize;
+        for fy in 0..frame.height {
+            let y = top + fy as isize;
+            if y < 0 || y >= height {
+                continue;
+            }
+
+            for fx in 0..frame.width {
+                let x = left + fx as isize;
+                let color_index = frame.pixels[fx + fy * frame.width];
+                if x < 0 || x >= width || color_index == 0 {
+                    continue;
+                }
+
+                bitmap.pixels[(x + y * width) as usize] = color_index;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use starcraft_assets::chk;
+    use starcraft_assets::cv5;
+    use starcraft_assets::dat::images::{ImageType, Images};
+    use starcraft_assets::dat::sprites::{SpriteType, Sprites};
+    use starcraft_assets::dat::Tables;
+    use starcraft_assets::fixtures;
+    use starcraft_assets::loader::{AssetLoader, Tileset};
+    use starcraft_assets::map::Map;
+    use starcraft_assets::memory::MemoryFileSystem;
+    use starcraft_assets::tbl::TBL;
+    use starcraft_assets::Assets;
+    use std::sync::Arc;
+
+    fn load(
+        fs: &MemoryFileSystem,
+        doodads: &[chk::Doodad],
+        sprites: &[chk::Sprite],
+    ) -> (Map, Assets) {
+        let map =
+            Map::from_chunks(&fixtures::chk(chk::Tileset::Jungle, 3, 2, doodads, sprites)).unwrap();
+        let loader = AssetLoader::new(Tileset::from(map.tileset.clone()), fs);
+        let assets = Assets::from(Arc::new(loader)).unwrap();
+
+        (map, assets)
+    }
+
+    #[test]
+    fn test_generate_bitmap_draws_every_minitile_pixel() {
+        let mut fs = MemoryFileSystem::new("fixtures");
+        fixtures::insert_tileset(&mut fs, Tileset::Jungle);
+        let (map, assets) = load(&fs, &[], &[]);
+        let bitmap = generate_bitmap(&map.dimensions, &map.mega_tile_ids, &assets).unwrap();
+        let width = map.dimensions.width * 32;
+        assert_eq!(bitmap.len(), width * map.dimensions.height * 32);
+
+        for (i, color) in bitmap.iter().enumerate() {
+            let (x, y) = (i % width, i / width);
+            let id = fixtures::mega_tile_id(x / 32, y / 32) as usize;
+            let mega_tile = fixtures::mega_tile(id >> 4, id & 0xF);
+            let (mini_tile, flipped) = fixtures::mini_tile(mega_tile, x % 32 / 8 + y % 32 / 8 * 4);
+            let column = if flipped { 7 - x % 8 } else { x % 8 };
+            // the synthetic palette is grayscale
+            let color_index = fixtures::color_index(mini_tile, column + y % 8 * 8);
+            assert_eq!(*color, [color_index; 3], "pixel ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn test_doodad_overlays_are_composited_over_the_terrain() {
+        let mut fs = MemoryFileSystem::new("fixtures");
+        fixtures::insert_tileset(&mut fs, Tileset::Jungle);
+        // doodad 3 draws sprite 5, mirrored, over 2x1 tiles
+        fs.insert(
+            "tileset\\jungle.cv5",
+            fixtures::cv5_with_doodad(&cv5::Doodad {
+                overlay_id: 5,
+                overlay_flags: 0x5,
+                string_index: 0,
+                dddata_index: 3,
+                width: 2,
+                height: 1,
+            }),
+        );
+        // a 4x2 tree top with a transparent corner
+        let tree = [0, 200, 201, 202, 203, 204, 205, 206];
+        fs.insert(
+            "unit\\thingy\\tileset\\jungle\\tree.grp",
+            fixtures::grp(4, 2, &tree),
+        );
+
+        let mut tables = Tables::default();
+        tables.sprites = Sprites(vec![SpriteType::default(); Sprites::COUNT]);
+        tables.sprites[5].image = 9;
+        tables.images = Images(vec![ImageType::default(); Images::COUNT]);
+        tables.images[9].grp = 1;
+        let images_tbl = TBL(vec!["thingy\\tileset\\jungle\\tree.grp".into()]);
+
+        let doodad = chk::Doodad {
+            id: 3,
+            x: 40,
+            y: 20,
+            owner: 0,
+            disabled: false,
+        };
+        // the same overlay in THG2 and a sprite without graphics
+        let sprites = [
+            chk::Sprite::new(5, 40, 20, 0, true),
+            chk::Sprite::new(6, 0, 0, 0, true),
+        ];
+        let (map, assets) = load(&fs, &[doodad], &sprites);
+        let overlays = map.overlays(&assets.cv5s);
+        let graphics = DatSpriteGraphics::load(&overlays, &tables, &images_tbl, &fs).unwrap();
+
+        let terrain = generate_indexed_bitmap(&map.dimensions, &map.mega_tile_ids, &assets)
+            .unwrap()
+            .pixels;
+        let bitmap = generate_indexed_map_bitmap(&map, &assets, &graphics).unwrap();
+        let width = bitmap.width;
+        for (i, color_index) in bitmap.pixels.iter().enumerate() {
+            let (x, y) = (i % width, i / width);
+            // the frame is centered on (40, 20) and mirrored
+            let expected = if (38..42).contains(&x) && (19..21).contains(&y) {
+                match tree[(41 - x) + (y - 19) * 4] {
+                    0 => terrain[i],
+                    tree_color_index => tree_color_index,
+                }
+            } else {
+                terrain[i]
+            };
+            assert_eq!(*color_index, expected, "pixel ({}, {})", x, y);
+        }
+
+        // mirrored, the transparent corner is at the top right
+        assert_eq!(bitmap.pixels[41 + 19 * width], terrain[41 + 19 * width]);
+        assert_eq!(bitmap.pixels[38 + 19 * width], 202);
+        let rgb = generate_map_bitmap(&map, &assets, &graphics).unwrap();
+        assert_eq!(rgb[38 + 19 * width], [202; 3]);
+    }
 }
@@ -0,0 +1,180 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use starcraft_assets::mpq::crypto::{self, HashType};
+use starcraft_assets::mpq::{MPQArchive, ReadOnlyMPQArchive, UnifiedMPQArchive};
+use std::io::{self, Write};
+use tempfile::NamedTempFile;
+
+// Roughly the sizes of tileset\jungle.*
+const FILES: [(&str, usize); 5] = [
+    ("tileset\\jungle.cv5", 1600 * 52),
+    ("tileset\\jungle.vf4", 5000 * 32),
+    ("tileset\\jungle.vx4", 5000 * 32),
+    ("tileset\\jungle.vr4", 32000 * 64),
+    ("tileset\\jungle.wpe", 256 * 4),
+];
+const HASH_TABLE_ENTRIES: usize = 16;
+const EXISTS: u32 = 0x8000_0000;
+
+fn synthetic(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+fn write_u32s(buf: &mut Vec<u8>, values: &[u32]) {
+    for value in values {
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// Writes an archive of uncompressed files, so the benchmarks measure reading rather
+/// than decompression
+fn synthetic_archive() -> NamedTempFile {
+    let mut archive = vec![0u8; 0x20];
+    let mut hash_table = vec![0xFFu8; HASH_TABLE_ENTRIES * 16];
+    let mut block_table = Vec::new();
+
+    for (block_index, (name, len)) in FILES.iter().enumerate() {
+        let offset = archive.len() as u32;
+        archive.extend(synthetic(*len));
+        write_u32s(
+            &mut block_table,
+            &[offset, *len as u32, *len as u32, EXISTS],
+        );
+
+        let mut slot = crypto::hash_string(name, HashType::TableOffset) as usize;
+        while hash_table[(slot % HASH_TABLE_ENTRIES) * 16 + 12] != 0xFF {
+            slot += 1;
+        }
+        let mut entry = Vec::new();
+        write_u32s(
+            &mut entry,
+            &[
+                crypto::hash_string(name, HashType::NameA),
+                crypto::hash_string(name, HashType::NameB),
+                0,
+                block_index as u32,
+            ],
+        );
+        let start = (slot % HASH_TABLE_ENTRIES) * 16;
+        hash_table[start..start + 16].copy_from_slice(&entry);
+    }
+
+    let hash_table_offset = archive.len() as u32;
+    crypto::encrypt(
+        &mut hash_table,
+        crypto::hash_string("(hash table)", HashType::FileKey),
+    );
+    archive.extend(hash_table);
+    let block_table_offset = archive.len() as u32;
+    crypto::encrypt(
+        &mut block_table,
+        crypto::hash_string("(block table)", HashType::FileKey),
+    );
+    archive.extend(block_table);
+
+    let mut header = Vec::new();
+    write_u32s(
+        &mut header,
+        &[
+            0x1A51_504D,
+            0x20,
+            archive.len() as u32,
+            3 << 16,
+            hash_table_offset,
+            block_table_offset,
+            HASH_TABLE_ENTRIES as u32,
+            FILES.len() as u32,
+        ],
+    );
+    archive[..0x20].copy_from_slice(&header);
+
+    // the target directory rather than /tmp, which may be a tmpfs that cannot be evicted
+    let mut file = NamedTempFile::new_in(env!("CARGO_TARGET_TMPDIR")).unwrap();
+    file.write_all(&archive).unwrap();
+    file
+}
+
+fn read_tileset(archive: &impl ReadOnlyMPQArchive) -> usize {
+    FILES
+        .iter()
+        .map(|(name, _)| archive.read_file(name).unwrap().len())
+        .sum()
+}
+
+/// Opening the archive for every load, as a map loader or tool does. The file stays in
+/// the page cache, so this measures opening and mapping rather than disk reads.
+fn reopen(c: &mut Criterion) {
+    let file = synthetic_archive();
+
+    c.bench_function("reopen file", |b| {
+        b.iter(|| read_tileset(&MPQArchive::open(file.path()).unwrap()))
+    });
+    c.bench_function("reopen mmap", |b| {
+        b.iter(|| read_tileset(&MPQArchive::map(file.path()).unwrap()))
+    });
+}
+
+/// Drops the archive from the page cache, so the next load reads it from disk
+#[cfg(target_os = "linux")]
+fn evict(path: &std::path::Path) {
+    use std::os::unix::io::AsRawFd;
+
+    let file = std::fs::File::open(path).unwrap();
+    // only clean pages can be dropped
+    file.sync_all().unwrap();
+    let result = unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+    assert_eq!(result, 0);
+}
+
+/// Loading an archive that is not in the page cache, as on the first launch after a
+/// boot. Only Linux can evict a single file, so this is skipped elsewhere.
+#[cfg(target_os = "linux")]
+fn cold(c: &mut Criterion) {
+    let file = synthetic_archive();
+
+    c.bench_function("cold file", |b| {
+        b.iter_batched(
+            || evict(file.path()),
+            |_| read_tileset(&MPQArchive::open(file.path()).unwrap()),
+            BatchSize::PerIteration,
+        )
+    });
+    c.bench_function("cold mmap", |b| {
+        b.iter_batched(
+            || evict(file.path()),
+            |_| read_tileset(&MPQArchive::map(file.path()).unwrap()),
+            BatchSize::PerIteration,
+        )
+    });
+}
+
+#[cfg(not(target_os = "linux"))]
+fn cold(_: &mut Criterion) {}
+
+/// Loading from archives that stay open, as the game does
+fn warm(c: &mut Criterion) {
+    let file = synthetic_archive();
+    let opened = MPQArchive::open(file.path()).unwrap();
+    let mapped = MPQArchive::map(file.path()).unwrap();
+
+    c.bench_function("warm file", |b| b.iter(|| read_tileset(&opened)));
+    c.bench_function("warm mmap", |b| b.iter(|| read_tileset(&mapped)));
+
+    let unified = UnifiedMPQArchive::from_existing(vec![MPQArchive::map(file.path()).unwrap()]);
+    c.bench_function("warm unified mmap", |b| {
+        b.iter(|| {
+            FILES
+                .iter()
+                .map(|(name, _)| unified.read_file(name).unwrap().len())
+                .sum::<usize>()
+        })
+    });
+    c.bench_function("warm mmap streamed", |b| {
+        b.iter(|| {
+            let mut file = mapped.open_file("tileset\\jungle.vr4").unwrap();
+            io::copy(&mut file, &mut io::sink()).unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, cold, reopen, warm);
+criterion_main!(benches);
@@ -0,0 +1,97 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use starcraft_assets::{cv5, loader, vf4, vr4, vx4, wpe, AssetLoader, Assets, Result};
+use std::io::Cursor;
+use std::mem::size_of_val;
+use std::sync::Arc;
+
+// Roughly the sizes of tileset\jungle.*
+const CV5_COUNT: usize = 1600;
+const VX4_COUNT: usize = 5000;
+const VR4_COUNT: usize = 32000;
+const WPE_COUNT: usize = 256;
+
+fn synthetic(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+struct SyntheticLoader {
+    cv5: Vec<u8>,
+    vf4: Vec<u8>,
+    vx4: Vec<u8>,
+    vr4: Vec<u8>,
+    wpe: Vec<u8>,
+}
+
+impl SyntheticLoader {
+    fn new() -> SyntheticLoader {
+        SyntheticLoader {
+            cv5: synthetic(CV5_COUNT * 52),
+            vf4: synthetic(VX4_COUNT * 32),
+            vx4: synthetic(VX4_COUNT * 32),
+            vr4: synthetic(VR4_COUNT * 64),
+            wpe: synthetic(WPE_COUNT * 4),
+        }
+    }
+}
+
+impl AssetLoader for SyntheticLoader {
+    fn tileset(&self) -> loader::Tileset {
+        loader::Tileset::Jungle
+    }
+
+    fn load_cv5s(&self) -> Result<cv5::CV5s> {
+        cv5::CV5s::from_buffer(&mut Cursor::new(&self.cv5))
+    }
+
+    fn load_vf4s(&self) -> Result<vf4::VF4s> {
+        vf4::VF4s::from_buffer(&mut Cursor::new(&self.vf4))
+    }
+
+    fn load_vx4s(&self) -> Result<vx4::VX4s> {
+        vx4::VX4s::from_buffer(&mut Cursor::new(&self.vx4))
+    }
+
+    fn load_vr4s(&self) -> Result<vr4::VR4s> {
+        vr4::VR4s::from_buffer(&mut Cursor::new(&self.vr4))
+    }
+
+    fn load_wpes(&self) -> Result<wpe::WPEs> {
+        wpe::WPEs::from_buffer(&mut Cursor::new(&self.wpe))
+    }
+}
+
+fn footprint(c: &mut Criterion) {
+    let loader = Arc::new(SyntheticLoader::new());
+    let assets = Assets::from(loader.clone()).unwrap();
+    let on_disk = loader.cv5.len()
+        + loader.vf4.len()
+        + loader.vx4.len()
+        + loader.vr4.len()
+        + loader.wpe.len();
+    let in_memory = size_of_val(&assets.cv5s.0[..])
+        + size_of_val(&assets.vf4s.0[..])
+        + size_of_val(&assets.vx4s.0[..])
+        + size_of_val(&assets.vr4s.0[..])
+        + size_of_val(&assets.wpes.0[..]);
+    println!(
+        "tileset footprint: {} bytes on disk, {} bytes in memory",
+        on_disk, in_memory
+    );
+
+    c.bench_function("Assets::from", |b| {
+        b.iter(|| Assets::from(loader.clone()).unwrap())
+    });
+}
+
+fn parsing(c: &mut Criterion) {
+    let loader = SyntheticLoader::new();
+
+    c.bench_function("cv5s", |b| b.iter(|| loader.load_cv5s().unwrap()));
+    c.bench_function("vf4s", |b| b.iter(|| loader.load_vf4s().unwrap()));
+    c.bench_function("vx4s", |b| b.iter(|| loader.load_vx4s().unwrap()));
+    c.bench_function("vr4s", |b| b.iter(|| loader.load_vr4s().unwrap()));
+    c.bench_function("wpes", |b| b.iter(|| loader.load_wpes().unwrap()));
+}
+
+criterion_group!(benches, footprint, parsing);
+criterion_main!(benches);
@@ -0,0 +1,119 @@
+use super::errors::*;
+use super::fs::ReadOnlyFileSystem;
+use super::loader::{AssetLoader, Tileset};
+use super::Assets;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Tileset assets shared across maps.
+///
+/// Each tileset is loaded from the file system the first time it is requested
+/// and every later request receives the same `Assets`. Failed loads are not
+/// cached, so a later request retries them.
+pub struct AssetCache<'a> {
+    fs: &'a dyn ReadOnlyFileSystem,
+    slots: HashMap<Tileset, Mutex<Option<Arc<Assets>>>>,
+}
+
+impl<'a> AssetCache<'a> {
+    pub fn new(fs: &'a dyn ReadOnlyFileSystem) -> AssetCache<'a> {
+        AssetCache {
+            fs,
+            slots: Tileset::VALUES
+                .iter()
+                .map(|tileset| (*tileset, Mutex::new(None)))
+                .collect(),
+        }
+    }
+
+    /// Returns the assets of `tileset`, loading them if this is the first request.
+    pub fn get(&self, tileset: Tileset) -> Result<Arc<Assets>> {
+        let mut slot = self.slots[&tileset]
+            .lock()
+            .map_err(|_| format!("asset cache for {} is poisoned", tileset))?;
+
+        if let Some(assets) = slot.as_ref() {
+            return Ok(assets.clone());
+        }
+
+        let asset_loader = AssetLoader::new(tileset, self.fs);
+        let assets = Arc::new(
+            Assets::from(Arc::new(asset_loader))
+                .chain_err(|| format!("failed to load {} tileset", tileset))?,
+        );
+        *slot = Some(assets.clone());
+
+        Ok(assets)
+    }
+
+    /// Loads every tileset that has not been loaded yet, in parallel.
+    pub fn preload(&self) -> Result<()> {
+        crossbeam::scope(|scope| {
+            let handles = Tileset::VALUES
+                .iter()
+                .map(|tileset| scope.spawn(move |_| self.get(*tileset)))
+                .collect::<Vec<_>>();
+
+            handles.into_iter().try_for_each(|handle| {
+                handle
+                    .join()
+                    .map_err(|_| Error::from("failed to preload tileset"))?
+                    .map(|_| ())
+            })
+        })
+        .map_err(|_| "failed to preload tilesets concurrently")?
+    }
+
+    pub fn is_loaded(&self, tileset: Tileset) -> bool {
+        self.slots[&tileset]
+            .lock()
+            .map(|slot| slot.is_some())
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingFileSystem {
+        reads: AtomicUsize,
+    }
+
+    impl ReadOnlyFileSystem for CountingFileSystem {
+        fn read(&self, _file_name: &str) -> Result<Vec<u8>> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![])
+        }
+
+        fn exists(&self, _file_name: &str) -> Result<bool> {
+            Ok(true)
+        }
+
+        fn size(&self, _file_name: &str) -> Result<u64> {
+            Ok(0)
+        }
+    }
+
+    #[test]
+    fn test_asset_cache_loads_each_tileset_once() {
+        let fs = CountingFileSystem {
+            reads: AtomicUsize::new(0),
+        };
+        let cache = AssetCache::new(&fs);
+
+        let jungle = cache.get(Tileset::Jungle).unwrap();
+        let again = cache.get(Tileset::Jungle).unwrap();
+
+        assert!(Arc::ptr_eq(&jungle, &again));
+        assert_eq!(jungle.tileset, Tileset::Jungle);
+        assert_eq!(fs.reads.load(Ordering::SeqCst), 5);
+        assert!(!cache.is_loaded(Tileset::Desert));
+
+        cache.preload().unwrap();
+
+        assert!(Tileset::VALUES.iter().all(|t| cache.is_loaded(*t)));
+        assert_eq!(fs.reads.load(Ordering::SeqCst), 5 * Tileset::VALUES.len());
+    }
+}
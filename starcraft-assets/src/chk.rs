@@ -52,6 +52,15 @@ pub enum ChunkName {
     Side,
     MegaTileIDs,
     StringData,
+    Doodads,
+    Sprites,
+    Units,
+    UnitSettings,
+    BroodWarUnitSettings,
+    UpgradeSettings,
+    BroodWarUpgradeSettings,
+    TechSettings,
+    BroodWarTechSettings,
 }
 
 impl Display for ChunkName {
@@ -71,6 +80,15 @@ impl ChunkName {
             "SIDE" => Some(ChunkName::Side),
             "MTXM" => Some(ChunkName::MegaTileIDs),
             "STR " => Some(ChunkName::StringData),
+            "DD2 " => Some(ChunkName::Doodads),
+            "THG2" => Some(ChunkName::Sprites),
+            "UNIT" => Some(ChunkName::Units),
+            "UNIS" => Some(ChunkName::UnitSettings),
+            "UNIx" => Some(ChunkName::BroodWarUnitSettings),
+            "UPGS" => Some(ChunkName::UpgradeSettings),
+            "UPGx" => Some(ChunkName::BroodWarUpgradeSettings),
+            "TECS" => Some(ChunkName::TechSettings),
+            "TECx" => Some(ChunkName::BroodWarTechSettings),
             _ => None,
         }
     }
@@ -85,6 +103,15 @@ impl ChunkName {
             ChunkName::Side => "SIDE",
             ChunkName::MegaTileIDs => "MTXM",
             ChunkName::StringData => "STR ",
+            ChunkName::Doodads => "DD2 ",
+            ChunkName::Sprites => "THG2",
+            ChunkName::Units => "UNIT",
+            ChunkName::UnitSettings => "UNIS",
+            ChunkName::BroodWarUnitSettings => "UNIx",
+            ChunkName::UpgradeSettings => "UPGS",
+            ChunkName::BroodWarUpgradeSettings => "UPGx",
+            ChunkName::TechSettings => "TECS",
+            ChunkName::BroodWarTechSettings => "TECx",
         }
     }
 }
@@ -99,6 +126,15 @@ pub enum Chunk {
     Sides(Vec<Side>),
     MegaTileIDs(Vec<MegaTileID>),
     StringData(StringData),
+    Doodads(Vec<Doodad>),
+    Sprites(Vec<Sprite>),
+    Units(Vec<Unit>),
+    UnitSettings(UnitSettings),
+    BroodWarUnitSettings(UnitSettings),
+    UpgradeSettings(UpgradeSettings),
+    BroodWarUpgradeSettings(UpgradeSettings),
+    TechSettings(TechSettings),
+    BroodWarTechSettings(TechSettings),
 }
 
 impl Chunk {
@@ -174,6 +210,75 @@ impl Chunk {
             ChunkName::StringData => {
                 StringData::from_buffer(cursor).map(|str_data| Some(Chunk::StringData(str_data)))
             }
+            ChunkName::Doodads => {
+                let doodad_count = header.size / Doodad::BLOCK_SIZE;
+                let mut doodads: Vec<Doodad> = Vec::with_capacity(doodad_count);
+                for _ in 0..doodad_count {
+                    doodads.push(Doodad::from_buffer(cursor)?);
+                }
+
+                Ok(Some(Chunk::Doodads(doodads)))
+            }
+            ChunkName::Sprites => {
+                let sprite_count = header.size / Sprite::BLOCK_SIZE;
+                let mut sprites: Vec<Sprite> = Vec::with_capacity(sprite_count);
+                for _ in 0..sprite_count {
+                    sprites.push(Sprite::from_buffer(cursor)?);
+                }
+
+                Ok(Some(Chunk::Sprites(sprites)))
+            }
+            ChunkName::Units => {
+                let unit_count = header.size / Unit::BLOCK_SIZE;
+                let mut units: Vec<Unit> = Vec::with_capacity(unit_count);
+                for _ in 0..unit_count {
+                    units.push(Unit::from_buffer(cursor)?);
+                }
+
+                Ok(Some(Chunk::Units(units)))
+            }
+            ChunkName::UnitSettings | ChunkName::BroodWarUnitSettings => {
+                let brood_war = header.name() == "UNIx";
+                let size = UnitSettings::size(brood_war);
+                if header.size != size {
+                    return Err(format!("{} size must be {} bytes", header.name(), size).into());
+                }
+
+                let settings = UnitSettings::from_buffer(cursor, brood_war)?;
+                Ok(Some(if brood_war {
+                    Chunk::BroodWarUnitSettings(settings)
+                } else {
+                    Chunk::UnitSettings(settings)
+                }))
+            }
+            ChunkName::UpgradeSettings | ChunkName::BroodWarUpgradeSettings => {
+                let brood_war = header.name() == "UPGx";
+                let size = UpgradeSettings::size(brood_war);
+                if header.size != size {
+                    return Err(format!("{} size must be {} bytes", header.name(), size).into());
+                }
+
+                let settings = UpgradeSettings::from_buffer(cursor, brood_war)?;
+                Ok(Some(if brood_war {
+                    Chunk::BroodWarUpgradeSettings(settings)
+                } else {
+                    Chunk::UpgradeSettings(settings)
+                }))
+            }
+            ChunkName::TechSettings | ChunkName::BroodWarTechSettings => {
+                let brood_war = header.name() == "TECx";
+                let size = TechSettings::size(brood_war);
+                if header.size != size {
+                    return Err(format!("{} size must be {} bytes", header.name(), size).into());
+                }
+
+                let settings = TechSettings::from_buffer(cursor, brood_war)?;
+                Ok(Some(if brood_war {
+                    Chunk::BroodWarTechSettings(settings)
+                } else {
+                    Chunk::TechSettings(settings)
+                }))
+            }
         }
     }
 }
@@ -424,6 +529,329 @@ impl StringData {
     }
 }
 
+/// A doodad placed by the map editor.
+///
+/// The doodad's MegaTiles are already part of MTXM; its overlay is placed separately in THG2.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Doodad {
+    /// The dddata index of the doodad's CV5 tile groups
+    pub id: u16,
+    /// X coordinate of the doodad's center in pixels
+    pub x: u16,
+    /// Y coordinate of the doodad's center in pixels
+    pub y: u16,
+    pub owner: u8,
+    pub disabled: bool,
+}
+
+impl Doodad {
+    const BLOCK_SIZE: usize = 8;
+
+    pub fn from_buffer(cursor: &mut Cursor<&Vec<u8>>) -> Result<Doodad> {
+        let position = cursor.position();
+        let mut read = || -> std::io::Result<Doodad> {
+            Ok(Doodad {
+                id: cursor.read_u16::<LittleEndian>()?,
+                x: cursor.read_u16::<LittleEndian>()?,
+                y: cursor.read_u16::<LittleEndian>()?,
+                owner: cursor.read_u8()?,
+                disabled: cursor.read_u8()? != 0,
+            })
+        };
+
+        read().chain_err(|| format!("failed to read doodad at position {}", position))
+    }
+}
+
+/// A sprite, or the sprite of a unit, placed on the map.
+///
+/// Doodad overlays such as tree tops are stored here.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Sprite {
+    /// sprites.dat id, or units.dat id if the sprite is a unit sprite
+    pub id: u16,
+    /// X coordinate of the sprite's center in pixels
+    pub x: u16,
+    /// Y coordinate of the sprite's center in pixels
+    pub y: u16,
+    pub owner: u8,
+    flags: u16,
+}
+
+impl Sprite {
+    const BLOCK_SIZE: usize = 10;
+    /// Flag of sprites whose `id` refers to sprites.dat
+    pub const DRAW_AS_SPRITE: u16 = 0x1000;
+    pub const DISABLED: u16 = 0x8000;
+
+    pub fn new(id: u16, x: u16, y: u16, owner: u8, is_pure_sprite: bool) -> Sprite {
+        Sprite {
+            id,
+            x,
+            y,
+            owner,
+            flags: if is_pure_sprite {
+                Sprite::DRAW_AS_SPRITE
+            } else {
+                0
+            },
+        }
+    }
+
+    pub fn from_buffer(cursor: &mut Cursor<&Vec<u8>>) -> Result<Sprite> {
+        let position = cursor.position();
+        let mut read = || -> std::io::Result<Sprite> {
+            let id = cursor.read_u16::<LittleEndian>()?;
+            let x = cursor.read_u16::<LittleEndian>()?;
+            let y = cursor.read_u16::<LittleEndian>()?;
+            let owner = cursor.read_u8()?;
+            cursor.read_u8()?;
+            let flags = cursor.read_u16::<LittleEndian>()?;
+
+            Ok(Sprite {
+                id,
+                x,
+                y,
+                owner,
+                flags,
+            })
+        };
+
+        read().chain_err(|| format!("failed to read sprite at position {}", position))
+    }
+
+    /// Whether `id` refers to sprites.dat rather than units.dat
+    pub fn is_pure_sprite(&self) -> bool {
+        self.flags & Sprite::DRAW_AS_SPRITE == Sprite::DRAW_AS_SPRITE
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.flags & Sprite::DISABLED == Sprite::DISABLED
+    }
+}
+
+/// A unit placed by the map editor, such as a mineral field or a start location.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Unit {
+    /// Serial number used to link units, e.g. a nydus canal to its exit
+    pub serial: u32,
+    /// X coordinate of the unit's center in pixels
+    pub x: u16,
+    /// Y coordinate of the unit's center in pixels
+    pub y: u16,
+    /// units.dat id
+    pub id: u16,
+    pub owner: u8,
+    /// Resources held by mineral fields and vespene geysers
+    pub resources: u32,
+}
+
+impl Unit {
+    const BLOCK_SIZE: usize = 36;
+
+    pub fn from_buffer(cursor: &mut Cursor<&Vec<u8>>) -> Result<Unit> {
+        let position = cursor.position();
+        let mut read = || -> std::io::Result<Unit> {
+            let serial = cursor.read_u32::<LittleEndian>()?;
+            let x = cursor.read_u16::<LittleEndian>()?;
+            let y = cursor.read_u16::<LittleEndian>()?;
+            let id = cursor.read_u16::<LittleEndian>()?;
+            // relation type, special properties and valid elements
+            cursor.set_position(cursor.position() + 6);
+            let owner = cursor.read_u8()?;
+            // hit points, shields and energy percentages
+            cursor.set_position(cursor.position() + 3);
+            let resources = cursor.read_u32::<LittleEndian>()?;
+            // hangar count, state flags, unused and the related unit's serial
+            cursor.set_position(cursor.position() + 12);
+
+            Ok(Unit {
+                serial,
+                x,
+                y,
+                id,
+                owner,
+                resources,
+            })
+        };
+
+        read().chain_err(|| format!("failed to read unit at position {}", position))
+    }
+}
+
+/// Reads `count` values of a settings section's column
+fn read_column<T>(
+    cursor: &mut Cursor<&Vec<u8>>,
+    count: usize,
+    read: impl Fn(&mut Cursor<&Vec<u8>>) -> std::io::Result<T>,
+) -> std::io::Result<Vec<T>> {
+    (0..count).map(|_| read(cursor)).collect()
+}
+
+/// Unit stats that a map overrides, from `UNIS` or its Brood War version `UNIx`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct UnitSettings {
+    /// Whether each unit keeps its units.dat stats and ignores the ones below
+    pub use_defaults: Vec<bool>,
+    /// In 256ths of a hit point
+    pub hit_points: Vec<u32>,
+    pub shields: Vec<u16>,
+    pub armor: Vec<u8>,
+    /// In frames
+    pub build_time: Vec<u16>,
+    pub mineral_cost: Vec<u16>,
+    pub gas_cost: Vec<u16>,
+    /// String of the unit's name, or 0 for the default
+    pub name_string: Vec<u16>,
+    /// Base damage of each weapon
+    pub weapon_damage: Vec<u16>,
+    /// Damage each weapon upgrade adds
+    pub weapon_bonus: Vec<u16>,
+}
+
+impl UnitSettings {
+    const UNIT_COUNT: usize = 228;
+    const WEAPON_COUNT: usize = 100;
+    const BROOD_WAR_WEAPON_COUNT: usize = 130;
+
+    fn weapon_count(brood_war: bool) -> usize {
+        if brood_war {
+            UnitSettings::BROOD_WAR_WEAPON_COUNT
+        } else {
+            UnitSettings::WEAPON_COUNT
+        }
+    }
+
+    pub fn size(brood_war: bool) -> usize {
+        UnitSettings::UNIT_COUNT * 16 + UnitSettings::weapon_count(brood_war) * 4
+    }
+
+    pub fn from_buffer(cursor: &mut Cursor<&Vec<u8>>, brood_war: bool) -> Result<UnitSettings> {
+        let position = cursor.position();
+        let units = UnitSettings::UNIT_COUNT;
+        let weapons = UnitSettings::weapon_count(brood_war);
+        let mut read = || -> std::io::Result<UnitSettings> {
+            Ok(UnitSettings {
+                use_defaults: read_column(cursor, units, |c| c.read_u8().map(|b| b != 0))?,
+                hit_points: read_column(cursor, units, |c| c.read_u32::<LittleEndian>())?,
+                shields: read_column(cursor, units, |c| c.read_u16::<LittleEndian>())?,
+                armor: read_column(cursor, units, |c| c.read_u8())?,
+                build_time: read_column(cursor, units, |c| c.read_u16::<LittleEndian>())?,
+                mineral_cost: read_column(cursor, units, |c| c.read_u16::<LittleEndian>())?,
+                gas_cost: read_column(cursor, units, |c| c.read_u16::<LittleEndian>())?,
+                name_string: read_column(cursor, units, |c| c.read_u16::<LittleEndian>())?,
+                weapon_damage: read_column(cursor, weapons, |c| c.read_u16::<LittleEndian>())?,
+                weapon_bonus: read_column(cursor, weapons, |c| c.read_u16::<LittleEndian>())?,
+            })
+        };
+
+        read().chain_err(|| format!("failed to read unit settings at position {}", position))
+    }
+}
+
+/// Upgrade costs that a map overrides, from `UPGS` or its Brood War version `UPGx`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct UpgradeSettings {
+    /// Whether each upgrade keeps its upgrades.dat costs and ignores the ones below
+    pub use_defaults: Vec<bool>,
+    pub mineral_cost: Vec<u16>,
+    /// Minerals added for each level after the first
+    pub mineral_factor: Vec<u16>,
+    pub gas_cost: Vec<u16>,
+    pub gas_factor: Vec<u16>,
+    /// In frames
+    pub research_time: Vec<u16>,
+    pub research_time_factor: Vec<u16>,
+}
+
+impl UpgradeSettings {
+    const UPGRADE_COUNT: usize = 46;
+    const BROOD_WAR_UPGRADE_COUNT: usize = 61;
+
+    fn upgrade_count(brood_war: bool) -> usize {
+        if brood_war {
+            UpgradeSettings::BROOD_WAR_UPGRADE_COUNT
+        } else {
+            UpgradeSettings::UPGRADE_COUNT
+        }
+    }
+
+    pub fn size(brood_war: bool) -> usize {
+        // UPGx pads its first column to an even length
+        UpgradeSettings::upgrade_count(brood_war) * 13 + brood_war as usize
+    }
+
+    pub fn from_buffer(cursor: &mut Cursor<&Vec<u8>>, brood_war: bool) -> Result<UpgradeSettings> {
+        let position = cursor.position();
+        let upgrades = UpgradeSettings::upgrade_count(brood_war);
+        let mut read = || -> std::io::Result<UpgradeSettings> {
+            let use_defaults = read_column(cursor, upgrades, |c| c.read_u8().map(|b| b != 0))?;
+            if brood_war {
+                cursor.read_u8()?;
+            }
+
+            Ok(UpgradeSettings {
+                use_defaults,
+                mineral_cost: read_column(cursor, upgrades, |c| c.read_u16::<LittleEndian>())?,
+                mineral_factor: read_column(cursor, upgrades, |c| c.read_u16::<LittleEndian>())?,
+                gas_cost: read_column(cursor, upgrades, |c| c.read_u16::<LittleEndian>())?,
+                gas_factor: read_column(cursor, upgrades, |c| c.read_u16::<LittleEndian>())?,
+                research_time: read_column(cursor, upgrades, |c| c.read_u16::<LittleEndian>())?,
+                research_time_factor: read_column(cursor, upgrades, |c| {
+                    c.read_u16::<LittleEndian>()
+                })?,
+            })
+        };
+
+        read().chain_err(|| format!("failed to read upgrade settings at position {}", position))
+    }
+}
+
+/// Technology costs that a map overrides, from `TECS` or its Brood War version `TECx`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct TechSettings {
+    /// Whether each technology keeps its techdata.dat costs and ignores the ones below
+    pub use_defaults: Vec<bool>,
+    pub mineral_cost: Vec<u16>,
+    pub gas_cost: Vec<u16>,
+    /// In frames
+    pub research_time: Vec<u16>,
+    pub energy_cost: Vec<u16>,
+}
+
+impl TechSettings {
+    const TECH_COUNT: usize = 24;
+    const BROOD_WAR_TECH_COUNT: usize = 44;
+
+    fn tech_count(brood_war: bool) -> usize {
+        if brood_war {
+            TechSettings::BROOD_WAR_TECH_COUNT
+        } else {
+            TechSettings::TECH_COUNT
+        }
+    }
+
+    pub fn size(brood_war: bool) -> usize {
+        TechSettings::tech_count(brood_war) * 9
+    }
+
+    pub fn from_buffer(cursor: &mut Cursor<&Vec<u8>>, brood_war: bool) -> Result<TechSettings> {
+        let position = cursor.position();
+        let techs = TechSettings::tech_count(brood_war);
+        let mut read = || -> std::io::Result<TechSettings> {
+            Ok(TechSettings {
+                use_defaults: read_column(cursor, techs, |c| c.read_u8().map(|b| b != 0))?,
+                mineral_cost: read_column(cursor, techs, |c| c.read_u16::<LittleEndian>())?,
+                gas_cost: read_column(cursor, techs, |c| c.read_u16::<LittleEndian>())?,
+                research_time: read_column(cursor, techs, |c| c.read_u16::<LittleEndian>())?,
+                energy_cost: read_column(cursor, techs, |c| c.read_u16::<LittleEndian>())?,
+            })
+        };
+
+        read().chain_err(|| format!("failed to read tech settings at position {}", position))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -441,4 +869,25 @@ mod tests {
 
         assert_eq!(Header::from_buffer(&mut cursor).is_err(), true);
     }
+
+    #[test]
+    fn test_sprites_chunk_reads_every_entry() {
+        let buf = [
+            &[0x2c, 0x01, 0x40, 0x00, 0x60, 0x00, 0x0b, 0x00, 0x00, 0x10][..],
+            &[0x07, 0x00, 0x10, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00][..],
+        ]
+        .concat();
+        let header = Header::new(*b"THG2", buf.len()).unwrap();
+        let mut cursor = Cursor::new(&buf);
+
+        let sprites = match Chunk::read(&header, &mut cursor).unwrap() {
+            Some(Chunk::Sprites(sprites)) => sprites,
+            _ => panic!("expected sprites"),
+        };
+
+        assert_eq!(sprites.len(), 2);
+        assert_eq!((sprites[0].id, sprites[0].x, sprites[0].y), (300, 64, 96));
+        assert!(sprites[0].is_pure_sprite());
+        assert!(!sprites[1].is_pure_sprite());
+    }
 }
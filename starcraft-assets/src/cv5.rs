@@ -1,29 +1,81 @@
 use super::errors::*;
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{ByteOrder, LittleEndian};
 use std::io::Cursor;
-use std::mem::MaybeUninit;
 use std::ops::{Index, IndexMut};
 
-/// A List of MegaTile references
-#[derive(Debug, Clone)]
-pub struct CV5(pub [usize; CV5::MEGA_TILE_REFERENCE_COUNT]);
+/// A tile group: its flags, edge or doodad data and a list of MegaTile references
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CV5 {
+    pub group_type: u16,
+    pub flags: u16,
+    data: [u16; CV5::DATA_COUNT],
+    pub megatiles: [u16; CV5::MEGA_TILE_REFERENCE_COUNT],
+}
 
 impl Index<usize> for CV5 {
-    type Output = usize;
+    type Output = u16;
 
     fn index(&self, i: usize) -> &Self::Output {
-        &self.0[i]
+        &self.megatiles[i]
     }
 }
 
 impl IndexMut<usize> for CV5 {
     fn index_mut(&mut self, i: usize) -> &mut Self::Output {
-        &mut self.0[i]
+        &mut self.megatiles[i]
     }
 }
 
 impl CV5 {
     const MEGA_TILE_REFERENCE_COUNT: usize = 16;
+    const DATA_COUNT: usize = 8;
+    const HAS_CREEP: u16 = 0x0040;
+    const UNBUILDABLE: u16 = 0x0080;
+
+    pub fn has_creep(&self) -> bool {
+        self.flags & CV5::HAS_CREEP == CV5::HAS_CREEP
+    }
+
+    pub fn is_buildable(&self) -> bool {
+        self.flags & CV5::UNBUILDABLE != CV5::UNBUILDABLE
+    }
+}
+
+/// The doodad specific part of a tile group.
+///
+/// Doodad groups describe a `width`x`height` block of MegaTiles, one row per group,
+/// and the sprite or unit that is drawn over it.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Doodad {
+    /// sprites.dat or units.dat id of the overlay
+    pub overlay_id: u16,
+    pub overlay_flags: u16,
+    /// stat_txt.tbl index of the doodad name
+    pub string_index: u16,
+    /// doodad id referenced by the CHK DD2 section
+    pub dddata_index: u16,
+    /// width in MegaTiles
+    pub width: u16,
+    /// height in MegaTiles
+    pub height: u16,
+}
+
+impl Doodad {
+    const OVERLAY_IS_SPRITE: u16 = 0x0001;
+    const OVERLAY_IS_UNIT: u16 = 0x0002;
+    const OVERLAY_IS_FLIPPED: u16 = 0x0004;
+
+    pub fn is_sprite_overlay(&self) -> bool {
+        self.overlay_flags & Doodad::OVERLAY_IS_SPRITE == Doodad::OVERLAY_IS_SPRITE
+    }
+
+    pub fn is_unit_overlay(&self) -> bool {
+        self.overlay_flags & Doodad::OVERLAY_IS_UNIT == Doodad::OVERLAY_IS_UNIT
+    }
+
+    pub fn is_overlay_flipped(&self) -> bool {
+        self.overlay_flags & Doodad::OVERLAY_IS_FLIPPED == Doodad::OVERLAY_IS_FLIPPED
+    }
 }
 
 pub struct CV5s(pub Vec<CV5>);
@@ -44,28 +96,97 @@ impl IndexMut<usize> for CV5s {
 
 impl CV5s {
     const BLOCK_SIZE: usize = 52;
+    const MEGA_TILE_REFERENCE_OFFSET: usize = 20;
+    /// Groups starting from this index are doodads
+    pub const DOODAD_GROUP_START: usize = 1024;
 
     pub fn from_buffer(cursor: &mut Cursor<&Vec<u8>>) -> Result<CV5s> {
-        let buf_size = cursor.get_ref().len();
-        let out_size = buf_size / CV5s::BLOCK_SIZE;
-        let mut cv5s = Vec::with_capacity(out_size);
-
-        cv5s.resize(out_size, unsafe { MaybeUninit::uninit().assume_init() });
-        for i in 0..out_size {
-            let previous_position = cursor.position();
-
-            cursor.set_position(cursor.position() + 20);
-            let mut megatile_references: [u16; CV5::MEGA_TILE_REFERENCE_COUNT] =
-                unsafe { MaybeUninit::uninit().assume_init() };
-            cursor
-                .read_u16_into::<LittleEndian>(&mut megatile_references)
-                .chain_err(|| format!("failed to read cv5 at position: '{}'", previous_position))?;
-            cv5s[i] = CV5(unsafe { MaybeUninit::uninit().assume_init() });
-            for j in 0..CV5::MEGA_TILE_REFERENCE_COUNT {
-                cv5s[i].0[j] = megatile_references[j] as usize;
-            }
+        let position = cursor.position() as usize;
+        let buf = cursor
+            .get_ref()
+            .get(position..)
+            .ok_or_else(|| format!("failed to read cv5 at position: '{}'", position))?;
+
+        let mut cv5s = Vec::with_capacity(buf.len() / CV5s::BLOCK_SIZE);
+        for block in buf.chunks_exact(CV5s::BLOCK_SIZE) {
+            let mut cv5 = CV5 {
+                group_type: LittleEndian::read_u16(&block[0..]),
+                flags: LittleEndian::read_u16(&block[2..]),
+                ..CV5::default()
+            };
+            LittleEndian::read_u16_into(&block[4..CV5s::MEGA_TILE_REFERENCE_OFFSET], &mut cv5.data);
+            LittleEndian::read_u16_into(
+                &block[CV5s::MEGA_TILE_REFERENCE_OFFSET..],
+                &mut cv5.megatiles,
+            );
+            cv5s.push(cv5);
         }
+        cursor.set_position((position + cv5s.len() * CV5s::BLOCK_SIZE) as u64);
+
+        Ok(CV5s(cv5s))
+    }
+
+    /// The doodad stored in tile group `group_index`, if that group is a doodad
+    pub fn doodad(&self, group_index: usize) -> Option<Doodad> {
+        if group_index < CV5s::DOODAD_GROUP_START {
+            return None;
+        }
+
+        self.0.get(group_index).map(|cv5| Doodad {
+            overlay_id: cv5.data[0],
+            overlay_flags: cv5.data[1],
+            string_index: cv5.data[2],
+            dddata_index: cv5.data[4],
+            width: cv5.data[5],
+            height: cv5.data[6],
+        })
+    }
+
+    /// Finds the first tile group of the doodad with the given DD2 id
+    pub fn find_doodad(&self, dddata_index: u16) -> Option<(usize, Doodad)> {
+        (CV5s::DOODAD_GROUP_START..self.0.len())
+            .filter_map(|i| self.doodad(i).map(|doodad| (i, doodad)))
+            .find(|(_, doodad)| doodad.dddata_index == dddata_index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cv5s_read_megatile_references_after_group_header() {
+        let mut buf = vec![0xffu8; CV5s::MEGA_TILE_REFERENCE_OFFSET];
+        for i in 0..CV5::MEGA_TILE_REFERENCE_COUNT as u16 {
+            buf.extend_from_slice(&(i + 0x100).to_le_bytes());
+        }
+        buf.extend_from_slice(&[0u8; 10]);
+        let mut cursor = Cursor::new(&buf);
+
+        let cv5s = CV5s::from_buffer(&mut cursor).unwrap();
+
+        assert_eq!(cv5s.0.len(), 1);
+        assert_eq!(cv5s[0][0], 0x100);
+        assert_eq!(cv5s[0][15], 0x10f);
+        assert_eq!(cv5s[0].flags, 0xffff);
+        assert_eq!(std::mem::size_of::<CV5>(), CV5s::BLOCK_SIZE);
+    }
+
+    #[test]
+    fn test_cv5s_expose_doodad_groups() {
+        let mut buf = vec![0u8; CV5s::BLOCK_SIZE * (CV5s::DOODAD_GROUP_START + 1)];
+        let header: [u16; 10] = [1, 0x0080, 212, 0x0001, 950, 0, 17, 4, 3, 0];
+        let doodad_offset = CV5s::BLOCK_SIZE * CV5s::DOODAD_GROUP_START;
+        LittleEndian::write_u16_into(&header, &mut buf[doodad_offset..doodad_offset + 20]);
+        let mut cursor = Cursor::new(&buf);
+
+        let cv5s = CV5s::from_buffer(&mut cursor).unwrap();
 
-        return Ok(CV5s(cv5s));
+        assert_eq!(cv5s.doodad(0), None);
+        let (group_index, doodad) = cv5s.find_doodad(17).unwrap();
+        assert_eq!(group_index, CV5s::DOODAD_GROUP_START);
+        assert_eq!(doodad.overlay_id, 212);
+        assert!(doodad.is_sprite_overlay());
+        assert_eq!((doodad.width, doodad.height), (4, 3));
     }
 }
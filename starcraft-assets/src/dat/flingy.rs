@@ -0,0 +1,22 @@
+dat! {
+    /// An entry of `flingy.dat`: the sprite of a unit or projectile and how it moves.
+    FlingyType, Flingies, "arr\\flingy.dat", 209 {
+        /// Index into `sprites.dat`
+        sprite: u16,
+        /// In 256ths of a pixel per frame
+        top_speed: u32,
+        acceleration: u16,
+        /// In 256ths of a pixel
+        halt_distance: u32,
+        turn_radius: u8,
+        unused: u8,
+        /// Whether the movement follows this entry, the unit's weapon or its iscript
+        movement_control: u8,
+    }
+}
+
+impl FlingyType {
+    pub const FLINGY_DAT: u8 = 0;
+    pub const PARTIALLY_MOBILE: u8 = 1;
+    pub const ISCRIPT: u8 = 2;
+}
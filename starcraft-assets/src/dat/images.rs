@@ -0,0 +1,218 @@
+use super::super::errors::*;
+use super::super::fs::ReadOnlyFileSystem;
+use super::super::grp::GRP;
+use super::super::tbl::TBL;
+use super::flingy::Flingies;
+use super::sprites::Sprites;
+use super::units::Units;
+use std::io::Cursor;
+
+dat! {
+    /// An entry of `images.dat`: a GRP, the iscript that animates it and its overlays.
+    ImageType, Images, "arr\\images.dat", 999 {
+        /// String of the GRP's path in `images.tbl`, relative to `unit\`
+        grp: u32,
+        /// Whether the GRP has frames for 17 directions that are mirrored to 32
+        graphic_turns: u8,
+        clickable: u8,
+        use_full_iscript: u8,
+        draw_if_cloaked: u8,
+        draw_function: u8,
+        /// Which remapping table `ImageType::REMAPPING` the image is drawn through
+        remapping: u8,
+        /// Entry in the header table of `iscript.bin`
+        iscript: u32,
+        /// Strings in `images.tbl` of the overlay files
+        shield_overlay: u32,
+        attack_overlay: u32,
+        damage_overlay: u32,
+        special_overlay: u32,
+        landing_dust_overlay: u32,
+        lift_off_dust_overlay: u32,
+    }
+}
+
+impl ImageType {
+    pub const NORMAL: u8 = 0;
+    pub const CLOAKED: u8 = 5;
+    pub const REMAP: u8 = 9;
+    pub const SHADOW: u8 = 10;
+    pub const SELECTION_CIRCLE: u8 = 13;
+    pub const HALLUCINATION: u8 = 16;
+
+    /// Names of the remapping tables in `tileset\<tileset>\`, starting at remapping 1
+    pub const REMAPPING: [&'static str; 4] = ["ofire.pcx", "gfire.pcx", "bfire.pcx", "bexpl.pcx"];
+
+    pub fn has_graphic_turns(&self) -> bool {
+        self.graphic_turns != 0
+    }
+
+    /// Path of the remapping table of tileset `tileset`, such as `jungle`
+    pub fn remapping_path(&self, tileset: &str) -> Option<String> {
+        (self.remapping as usize)
+            .checked_sub(1)
+            .and_then(|i| ImageType::REMAPPING.get(i))
+            .map(|name| format!("tileset\\{}\\{}", tileset, name))
+    }
+
+    /// Path of the GRP, given `images.tbl`
+    pub fn grp_path(&self, images_tbl: &TBL) -> Option<String> {
+        ImageType::path(self.grp, images_tbl)
+    }
+
+    /// Path of an overlay such as `shield_overlay`, given `images.tbl`
+    pub fn overlay_path(overlay: u32, images_tbl: &TBL) -> Option<String> {
+        ImageType::path(overlay, images_tbl)
+    }
+
+    fn path(id: u32, images_tbl: &TBL) -> Option<String> {
+        if id > u16::MAX as u32 {
+            return None;
+        }
+
+        images_tbl
+            .by_id(id as u16)
+            .map(|path| format!("unit\\{}", path))
+    }
+}
+
+/// What a unit is drawn with, followed from `units.dat` through `flingy.dat` and
+/// `sprites.dat` to `images.dat`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UnitGraphics {
+    /// None for sprites placed without a unit, such as doodad overlays
+    pub flingy: Option<usize>,
+    pub sprite: usize,
+    pub image: usize,
+    pub grp_path: String,
+    /// Entry in the header table of `iscript.bin`
+    pub iscript: u32,
+}
+
+impl UnitGraphics {
+    /// Follows the chain for unit `unit_id`, or None if any link is out of range
+    pub fn resolve(
+        unit_id: usize,
+        units: &Units,
+        flingies: &Flingies,
+        sprites: &Sprites,
+        images: &Images,
+        images_tbl: &TBL,
+    ) -> Option<UnitGraphics> {
+        let flingy = units.0.get(unit_id)?.flingy as usize;
+        let sprite = flingies.0.get(flingy)?.sprite as usize;
+
+        Some(UnitGraphics {
+            flingy: Some(flingy),
+            ..UnitGraphics::resolve_sprite(sprite, sprites, images, images_tbl)?
+        })
+    }
+
+    /// Follows the chain from sprite `sprite_id`, as for sprites placed without a unit
+    pub fn resolve_sprite(
+        sprite_id: usize,
+        sprites: &Sprites,
+        images: &Images,
+        images_tbl: &TBL,
+    ) -> Option<UnitGraphics> {
+        let image = sprites.0.get(sprite_id)?.image as usize;
+        let image_type = images.0.get(image)?;
+
+        Some(UnitGraphics {
+            flingy: None,
+            sprite: sprite_id,
+            image,
+            grp_path: image_type.grp_path(images_tbl)?,
+            iscript: image_type.iscript,
+        })
+    }
+
+    pub fn load_grp(&self, fs: &dyn ReadOnlyFileSystem) -> Result<GRP> {
+        let buf = fs.read(&self.grp_path)?;
+
+        GRP::from_buffer(&mut Cursor::new(&buf))
+            .chain_err(|| format!("failed to read grp {}", self.grp_path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::memory::MemoryFileSystem;
+    use super::super::flingy::FlingyType;
+    use super::super::sprites::SpriteType;
+    use super::super::units::UnitType;
+    use super::*;
+
+    #[test]
+    fn test_unit_graphics_follow_the_chain_to_a_grp() {
+        let mut units = Units(vec![UnitType::default(); Units::COUNT]);
+        units[0].flingy = 2;
+        let mut flingies = Flingies(vec![FlingyType::default(); Flingies::COUNT]);
+        flingies[2].sprite = 300;
+        let mut sprites = Sprites(vec![SpriteType::default(); Sprites::COUNT]);
+        sprites[300] = SpriteType {
+            image: 7,
+            health_bar: 19,
+            selection_circle: 2,
+            ..SpriteType::default()
+        };
+        let mut images = Images(vec![ImageType::default(); Images::COUNT]);
+        images[7] = ImageType {
+            grp: 2,
+            iscript: 5,
+            remapping: 1,
+            ..ImageType::default()
+        };
+
+        // every table survives its vanilla layout
+        let mut buf = Vec::new();
+        sprites.write(&mut buf).unwrap();
+        assert_eq!(buf.len(), 3229);
+        assert_eq!(
+            Sprites::from_buffer(&mut Cursor::new(&buf)).unwrap(),
+            sprites
+        );
+        let mut buf = Vec::new();
+        flingies.write(&mut buf).unwrap();
+        assert_eq!(buf.len(), 3135);
+        let mut buf = Vec::new();
+        images.write(&mut buf).unwrap();
+        assert_eq!(buf.len(), 37962);
+
+        let images_tbl = TBL(vec!["terran\\scv.grp".into(), "terran\\marine.grp".into()]);
+        let graphics =
+            UnitGraphics::resolve(0, &units, &flingies, &sprites, &images, &images_tbl).unwrap();
+        assert_eq!(
+            graphics,
+            UnitGraphics {
+                flingy: Some(2),
+                sprite: 300,
+                image: 7,
+                grp_path: "unit\\terran\\marine.grp".into(),
+                iscript: 5,
+            }
+        );
+        assert_eq!(
+            UnitGraphics::resolve_sprite(300, &sprites, &images, &images_tbl),
+            Some(UnitGraphics {
+                flingy: None,
+                ..graphics.clone()
+            })
+        );
+        assert_eq!(sprites[300].health_bar_boxes(), 6);
+        assert_eq!(sprites[300].selection_circle_image(), 563);
+        assert_eq!(
+            images[7].remapping_path("jungle").as_deref(),
+            Some("tileset\\jungle\\ofire.pcx")
+        );
+
+        let mut fs = MemoryFileSystem::new("graphics");
+        fs.insert("unit\\terran\\marine.grp", vec![0, 0, 4, 0, 4, 0]);
+        assert_eq!(graphics.load_grp(&fs).unwrap().width, 4);
+        images[7].grp = 3;
+        assert_eq!(
+            UnitGraphics::resolve(0, &units, &flingies, &sprites, &images, &images_tbl),
+            None
+        );
+    }
+}
@@ -0,0 +1,40 @@
+use super::super::tbl::TBL;
+
+dat! {
+    /// An entry of `mapdata.dat`: a campaign mission.
+    CampaignMap, CampaignMaps, "arr\\mapdata.dat", 65 {
+        /// String of the mission's directory in `mapdata.tbl`
+        directory: u32,
+    }
+}
+
+impl CampaignMap {
+    /// Directory of the mission, given `mapdata.tbl`
+    pub fn path<'a>(&self, mapdata_tbl: &'a TBL) -> Option<&'a str> {
+        if self.directory > u16::MAX as u32 {
+            return None;
+        }
+
+        mapdata_tbl.by_id(self.directory as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_campaign_maps_resolve_their_directories() {
+        let mut maps = CampaignMaps(vec![CampaignMap::default(); CampaignMaps::COUNT]);
+        maps[0].directory = 1;
+        let mut buf = Vec::new();
+        maps.write(&mut buf).unwrap();
+        assert_eq!(buf.len(), 260);
+        let maps = CampaignMaps::from_buffer(&mut Cursor::new(&buf)).unwrap();
+
+        let tbl = TBL(vec!["campaign\\terran\\terran01".into()]);
+        assert_eq!(maps[0].path(&tbl), Some("campaign\\terran\\terran01"));
+        assert_eq!(maps[1].path(&tbl), None);
+    }
+}
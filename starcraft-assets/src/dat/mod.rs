@@ -0,0 +1,642 @@
+//! The game's data tables in `arr\*.dat`.
+//!
+//! A DAT file stores its entries column by column: every entry's first field, then every
+//! entry's second field, and so on. Some columns only cover a range of the entries. Mods
+//! that add entries use the extended layout, where every column covers every entry and
+//! the entry count follows from the file size.
+
+use super::errors::*;
+use super::fs::ReadOnlyFileSystem;
+use byteorder::{ByteOrder, LittleEndian};
+use std::fmt::{Debug, Display, Formatter};
+use std::io::Cursor;
+use std::ops::Range;
+
+/// Declares an entry struct and its table, with a reader and writer for both layouts.
+macro_rules! dat {
+    (@range) => {
+        None
+    };
+    (@range $range:expr) => {
+        Some($range)
+    };
+    (
+        $(#[$entry_meta:meta])*
+        $entry:ident, $table:ident, $file_name:expr, $count:literal {
+            $($(#[$field_meta:meta])* $field:ident: $ty:ty $([$range:expr])?,)*
+        }
+    ) => {
+        $(#[$entry_meta])*
+        #[derive(Debug, Clone, Default, Eq, PartialEq)]
+        pub struct $entry {
+            $($(#[$field_meta])* pub $field: $ty,)*
+        }
+
+        impl $entry {
+            /// Every field's name and value, in file order
+            pub fn fields(&self) -> Vec<(&'static str, String)> {
+                vec![$((stringify!($field), format!("{:?}", self.$field)),)*]
+            }
+        }
+
+        #[derive(Debug, Clone, Default, Eq, PartialEq)]
+        pub struct $table(pub Vec<$entry>);
+
+        impl std::ops::Index<usize> for $table {
+            type Output = $entry;
+
+            fn index(&self, i: usize) -> &Self::Output {
+                &self.0[i]
+            }
+        }
+
+        impl std::ops::IndexMut<usize> for $table {
+            fn index_mut(&mut self, i: usize) -> &mut Self::Output {
+                &mut self.0[i]
+            }
+        }
+
+        impl $table {
+            pub const FILE_NAME: &'static str = $file_name;
+            /// Entries in the game's own file
+            pub const COUNT: usize = $count;
+
+            fn columns() -> Vec<$crate::dat::Column> {
+                vec![$($crate::dat::Column::new::<$ty>(dat!(@range $($range)?)),)*]
+            }
+
+            pub fn from_buffer(
+                cursor: &mut std::io::Cursor<&Vec<u8>>,
+            ) -> $crate::errors::Result<$table> {
+                let mut reader = $crate::dat::ColumnReader::new(
+                    cursor,
+                    Self::FILE_NAME,
+                    Self::COUNT,
+                    &Self::columns(),
+                )?;
+                let mut entries = vec![$entry::default(); reader.count()];
+                $(reader.read(&mut entries, |entry| &mut entry.$field);)*
+
+                Ok($table(entries))
+            }
+
+            /// Size of the game's file, in the vanilla layout
+            pub fn vanilla_size() -> usize {
+                $crate::dat::vanilla_size(Self::COUNT, &Self::columns())
+            }
+
+            /// Writes the table in the vanilla layout if it has as many entries as the
+            /// game's file, and in the extended layout if it has more
+            pub fn write(&self, writer: &mut impl std::io::Write) -> $crate::errors::Result<()> {
+                use $crate::errors::ResultExt;
+
+                if self.0.len() < Self::COUNT {
+                    return Err(format!(
+                        "{} needs at least {} entries, not {}",
+                        Self::FILE_NAME,
+                        Self::COUNT,
+                        self.0.len()
+                    )
+                    .into());
+                }
+
+                let mut columns =
+                    $crate::dat::ColumnWriter::new(Self::COUNT, &Self::columns(), self.0.len());
+                $(columns.write(&self.0, |entry| entry.$field);)*
+
+                writer
+                    .write_all(&columns.finish())
+                    .chain_err(|| format!("failed to write {}", Self::FILE_NAME))
+            }
+
+            /// Reads the table from `fs`, keeping not found errors distinguishable
+            pub fn load(
+                fs: &dyn $crate::fs::ReadOnlyFileSystem,
+            ) -> $crate::errors::Result<$table> {
+                use $crate::errors::ResultExt;
+
+                let buf = fs.read(Self::FILE_NAME)?;
+                $table::from_buffer(&mut std::io::Cursor::new(&buf))
+                    .chain_err(|| format!("failed to read {}", Self::FILE_NAME))
+            }
+
+            /// Every field that differs in `other`, including the fields of entries
+            /// that only one of the tables has
+            pub fn diff(&self, other: &$table) -> Vec<$crate::dat::Change> {
+                $crate::dat::diff(Self::FILE_NAME, &self.0, &other.0, $entry::fields)
+            }
+        }
+    };
+}
+
+pub mod flingy;
+pub mod images;
+pub mod mapdata;
+pub mod orders;
+pub mod portdata;
+pub mod sfxdata;
+pub mod sprites;
+pub mod techdata;
+pub mod units;
+pub mod upgrades;
+pub mod weapons;
+
+/// A field of a DAT entry that differs between two tables.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Change {
+    pub file_name: &'static str,
+    pub entry: usize,
+    pub field: &'static str,
+    /// The value before, or None if the entry was added
+    pub old: Option<String>,
+    /// The value after, or None if the entry was removed
+    pub new: Option<String>,
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter) -> std::result::Result<(), std::fmt::Error> {
+        let value = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".into());
+        write!(
+            f,
+            "{} {} {}: {} -> {}",
+            self.file_name,
+            self.entry,
+            self.field,
+            value(&self.old),
+            value(&self.new)
+        )
+    }
+}
+
+pub(crate) fn diff<E>(
+    file_name: &'static str,
+    old: &[E],
+    new: &[E],
+    fields: impl Fn(&E) -> Vec<(&'static str, String)>,
+) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for entry in 0..old.len().max(new.len()) {
+        let old_fields = old.get(entry).map(&fields).unwrap_or_default();
+        let new_fields = new.get(entry).map(&fields).unwrap_or_default();
+        let names = if old_fields.is_empty() {
+            &new_fields
+        } else {
+            &old_fields
+        };
+
+        for (i, (field, _)) in names.iter().enumerate() {
+            let old = old_fields.get(i).map(|(_, value)| value.clone());
+            let new = new_fields.get(i).map(|(_, value)| value.clone());
+            if old != new {
+                changes.push(Change {
+                    file_name,
+                    entry,
+                    field,
+                    old,
+                    new,
+                });
+            }
+        }
+    }
+
+    changes
+}
+
+/// A DAT that a mod replaced.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct AppliedDat {
+    pub file_name: &'static str,
+    /// Whether the file's size only fit the extended layout, which mods use to add entries
+    pub extended: bool,
+}
+
+/// Every DAT table the game loads.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Tables {
+    pub units: units::Units,
+    pub weapons: weapons::Weapons,
+    pub flingies: flingy::Flingies,
+    pub sprites: sprites::Sprites,
+    pub images: images::Images,
+    pub upgrades: upgrades::Upgrades,
+    pub techs: techdata::Techs,
+    pub orders: orders::Orders,
+    pub sound_effects: sfxdata::SoundEffects,
+    pub portraits: portdata::Portraits,
+    pub campaign_maps: mapdata::CampaignMaps,
+}
+
+impl Tables {
+    /// Reads every table from `fs`, such as the game's archives
+    pub fn load(fs: &dyn ReadOnlyFileSystem) -> Result<Tables> {
+        Ok(Tables {
+            units: units::Units::load(fs)?,
+            weapons: weapons::Weapons::load(fs)?,
+            flingies: flingy::Flingies::load(fs)?,
+            sprites: sprites::Sprites::load(fs)?,
+            images: images::Images::load(fs)?,
+            upgrades: upgrades::Upgrades::load(fs)?,
+            techs: techdata::Techs::load(fs)?,
+            orders: orders::Orders::load(fs)?,
+            sound_effects: sfxdata::SoundEffects::load(fs)?,
+            portraits: portdata::Portraits::load(fs)?,
+            campaign_maps: mapdata::CampaignMaps::load(fs)?,
+        })
+    }
+
+    /// Replaces every table that a mod in `fs` ships a DAT for, in either layout.
+    /// Returns the files that were applied and the layout each was read in.
+    pub fn apply_mod(&mut self, fs: &dyn ReadOnlyFileSystem) -> Result<Vec<AppliedDat>> {
+        let mut applied = Vec::new();
+        macro_rules! replace {
+            ($($field:ident: $table:ty,)*) => {$(
+                match <$table>::load(fs) {
+                    Ok(table) => {
+                        self.$field = table;
+                        applied.push(AppliedDat {
+                            file_name: <$table>::FILE_NAME,
+                            extended: fs.size(<$table>::FILE_NAME)?
+                                != <$table>::vanilla_size() as u64,
+                        });
+                    }
+                    Err(ref err) if err.is_not_found() => {}
+                    Err(err) => return Err(err),
+                }
+            )*};
+        }
+
+        replace!(
+            units: units::Units,
+            weapons: weapons::Weapons,
+            flingies: flingy::Flingies,
+            sprites: sprites::Sprites,
+            images: images::Images,
+            upgrades: upgrades::Upgrades,
+            techs: techdata::Techs,
+            orders: orders::Orders,
+            sound_effects: sfxdata::SoundEffects,
+            portraits: portdata::Portraits,
+            campaign_maps: mapdata::CampaignMaps,
+        );
+
+        Ok(applied)
+    }
+
+    /// The tables with a mod in `fs` applied
+    pub fn with_mod(&self, fs: &dyn ReadOnlyFileSystem) -> Result<Tables> {
+        let mut tables = self.clone();
+        tables.apply_mod(fs)?;
+
+        Ok(tables)
+    }
+
+    /// Every field that differs in `other`, table by table
+    pub fn diff(&self, other: &Tables) -> Vec<Change> {
+        [
+            self.units.diff(&other.units),
+            self.weapons.diff(&other.weapons),
+            self.flingies.diff(&other.flingies),
+            self.sprites.diff(&other.sprites),
+            self.images.diff(&other.images),
+            self.upgrades.diff(&other.upgrades),
+            self.techs.diff(&other.techs),
+            self.orders.diff(&other.orders),
+            self.sound_effects.diff(&other.sound_effects),
+            self.portraits.diff(&other.portraits),
+            self.campaign_maps.diff(&other.campaign_maps),
+        ]
+        .concat()
+    }
+}
+
+/// A value that a DAT column stores for each entry.
+pub(crate) trait Field: Copy + Default + Debug {
+    const SIZE: usize;
+
+    fn read(buf: &[u8]) -> Self;
+    fn write(self, buf: &mut Vec<u8>);
+}
+
+impl Field for u8 {
+    const SIZE: usize = 1;
+
+    fn read(buf: &[u8]) -> Self {
+        buf[0]
+    }
+
+    fn write(self, buf: &mut Vec<u8>) {
+        buf.push(self);
+    }
+}
+
+impl Field for u16 {
+    const SIZE: usize = 2;
+
+    fn read(buf: &[u8]) -> Self {
+        LittleEndian::read_u16(buf)
+    }
+
+    fn write(self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl Field for u32 {
+    const SIZE: usize = 4;
+
+    fn read(buf: &[u8]) -> Self {
+        LittleEndian::read_u32(buf)
+    }
+
+    fn write(self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+/// A width and height in pixels.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Size {
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Field for Size {
+    const SIZE: usize = 4;
+
+    fn read(buf: &[u8]) -> Self {
+        Size {
+            width: LittleEndian::read_u16(buf),
+            height: LittleEndian::read_u16(&buf[2..]),
+        }
+    }
+
+    fn write(self, buf: &mut Vec<u8>) {
+        self.width.write(buf);
+        self.height.write(buf);
+    }
+}
+
+/// Distances in pixels from an entity's center to the edges of its collision box.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Bounds {
+    pub left: u16,
+    pub up: u16,
+    pub right: u16,
+    pub down: u16,
+}
+
+impl Field for Bounds {
+    const SIZE: usize = 8;
+
+    fn read(buf: &[u8]) -> Self {
+        Bounds {
+            left: LittleEndian::read_u16(buf),
+            up: LittleEndian::read_u16(&buf[2..]),
+            right: LittleEndian::read_u16(&buf[4..]),
+            down: LittleEndian::read_u16(&buf[6..]),
+        }
+    }
+
+    fn write(self, buf: &mut Vec<u8>) {
+        for value in &[self.left, self.up, self.right, self.down] {
+            value.write(buf);
+        }
+    }
+}
+
+/// The size of a column's values and the entries it covers in the vanilla layout.
+pub(crate) struct Column {
+    size: usize,
+    range: Option<Range<usize>>,
+}
+
+impl Column {
+    pub(crate) fn new<T: Field>(range: Option<Range<usize>>) -> Column {
+        Column {
+            size: T::SIZE,
+            range,
+        }
+    }
+
+    fn range(&self, count: usize, extended: bool) -> Range<usize> {
+        match &self.range {
+            Some(range) if !extended => range.clone(),
+            _ => 0..count,
+        }
+    }
+}
+
+/// Size of a DAT in the vanilla layout
+pub(crate) fn vanilla_size(count: usize, columns: &[Column]) -> usize {
+    columns
+        .iter()
+        .map(|column| column.size * column.range(count, false).len())
+        .sum()
+}
+
+pub(crate) struct ColumnReader<'a> {
+    buf: &'a [u8],
+    position: usize,
+    count: usize,
+    ranges: std::vec::IntoIter<Range<usize>>,
+}
+
+impl<'a> ColumnReader<'a> {
+    /// Works out the layout of the DAT from its size. The rest of the buffer is the DAT.
+    pub(crate) fn new(
+        cursor: &'a mut Cursor<&Vec<u8>>,
+        file_name: &str,
+        count: usize,
+        columns: &[Column],
+    ) -> Result<ColumnReader<'a>> {
+        let start = cursor.position() as usize;
+        let buf = cursor
+            .get_ref()
+            .get(start..)
+            .ok_or_else(|| format!("failed to read {} at position: '{}'", file_name, start))?;
+
+        let vanilla_size = vanilla_size(count, columns);
+        let entry_size = columns.iter().map(|column| column.size).sum::<usize>();
+        let (count, extended) = if buf.len() == vanilla_size {
+            (count, false)
+        } else if buf.len() >= count * entry_size && buf.len() % entry_size == 0 {
+            (buf.len() / entry_size, true)
+        } else {
+            return Err(format!(
+                "{} of {} bytes is neither {} bytes nor {} or more entries of {}",
+                file_name,
+                buf.len(),
+                vanilla_size,
+                count,
+                entry_size
+            )
+            .into());
+        };
+        let ranges = columns
+            .iter()
+            .map(|column| column.range(count, extended))
+            .collect::<Vec<_>>();
+        cursor.set_position(cursor.get_ref().len() as u64);
+
+        Ok(ColumnReader {
+            buf: &cursor.get_ref()[start..],
+            position: 0,
+            count,
+            ranges: ranges.into_iter(),
+        })
+    }
+
+    /// Entries in the file
+    pub(crate) fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Reads the next column into the field that `field` selects
+    pub(crate) fn read<E, T: Field>(
+        &mut self,
+        entries: &mut [E],
+        field: impl Fn(&mut E) -> &mut T,
+    ) {
+        let range = self.ranges.next().expect("a range for every column");
+        for entry in &mut entries[range] {
+            *field(entry) = T::read(&self.buf[self.position..]);
+            self.position += T::SIZE;
+        }
+    }
+}
+
+pub(crate) struct ColumnWriter {
+    buf: Vec<u8>,
+    ranges: std::vec::IntoIter<Range<usize>>,
+}
+
+impl ColumnWriter {
+    pub(crate) fn new(count: usize, columns: &[Column], len: usize) -> ColumnWriter {
+        // tables with the vanilla entry count are written the way the game ships them
+        let extended = len != count;
+        let ranges = columns
+            .iter()
+            .map(|column| column.range(len, extended))
+            .collect::<Vec<_>>();
+
+        ColumnWriter {
+            buf: Vec::new(),
+            ranges: ranges.into_iter(),
+        }
+    }
+
+    /// Writes the next column from the field that `field` selects
+    pub(crate) fn write<E, T: Field>(&mut self, entries: &[E], field: impl Fn(&E) -> T) {
+        let range = self.ranges.next().expect("a range for every column");
+        for entry in &entries[range] {
+            field(entry).write(&mut self.buf);
+        }
+    }
+
+    pub(crate) fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::memory::MemoryFileSystem;
+    use super::*;
+
+    #[test]
+    fn test_mods_replace_tables_and_diff_by_field() {
+        let tables = Tables {
+            units: units::Units(vec![Default::default(); units::Units::COUNT]),
+            weapons: weapons::Weapons(vec![Default::default(); weapons::Weapons::COUNT]),
+            flingies: flingy::Flingies(vec![Default::default(); flingy::Flingies::COUNT]),
+            sprites: sprites::Sprites(vec![Default::default(); sprites::Sprites::COUNT]),
+            images: images::Images(vec![Default::default(); images::Images::COUNT]),
+            upgrades: upgrades::Upgrades(vec![Default::default(); upgrades::Upgrades::COUNT]),
+            techs: techdata::Techs(vec![Default::default(); techdata::Techs::COUNT]),
+            orders: orders::Orders(vec![Default::default(); orders::Orders::COUNT]),
+            sound_effects: sfxdata::SoundEffects(vec![
+                Default::default();
+                sfxdata::SoundEffects::COUNT
+            ]),
+            portraits: portdata::Portraits(vec![Default::default(); portdata::Portraits::COUNT]),
+            campaign_maps: mapdata::CampaignMaps(vec![
+                Default::default();
+                mapdata::CampaignMaps::COUNT
+            ]),
+        };
+        let mut vanilla = MemoryFileSystem::new("vanilla");
+        macro_rules! insert {
+            ($fs:expr, $table:expr, $type:ty) => {{
+                let mut buf = Vec::new();
+                $table.write(&mut buf).unwrap();
+                $fs.insert(<$type>::FILE_NAME, buf);
+            }};
+        }
+        insert!(vanilla, tables.units, units::Units);
+        insert!(vanilla, tables.weapons, weapons::Weapons);
+        insert!(vanilla, tables.flingies, flingy::Flingies);
+        insert!(vanilla, tables.sprites, sprites::Sprites);
+        insert!(vanilla, tables.images, images::Images);
+        insert!(vanilla, tables.upgrades, upgrades::Upgrades);
+        insert!(vanilla, tables.techs, techdata::Techs);
+        insert!(vanilla, tables.orders, orders::Orders);
+        insert!(vanilla, tables.sound_effects, sfxdata::SoundEffects);
+        insert!(vanilla, tables.portraits, portdata::Portraits);
+        insert!(vanilla, tables.campaign_maps, mapdata::CampaignMaps);
+        let vanilla = Tables::load(&vanilla).unwrap();
+        assert_eq!(vanilla, tables);
+
+        // a balance change, and an extended weapons.dat with one more weapon
+        let mut modded = tables.clone();
+        modded.units[0].hit_points = 40 << 8;
+        modded.weapons.0.push(weapons::WeaponType {
+            damage: 5,
+            ..Default::default()
+        });
+        let mut fs = MemoryFileSystem::new("mod");
+        insert!(fs, modded.units, units::Units);
+        insert!(fs, modded.weapons, weapons::Weapons);
+
+        let mut applied = vanilla.clone();
+        assert_eq!(
+            applied.apply_mod(&fs).unwrap(),
+            vec![
+                AppliedDat {
+                    file_name: units::Units::FILE_NAME,
+                    extended: false,
+                },
+                AppliedDat {
+                    file_name: weapons::Weapons::FILE_NAME,
+                    extended: true,
+                },
+            ]
+        );
+        assert_eq!(applied, modded);
+
+        let changes = vanilla.diff(&applied);
+        assert_eq!(changes.len(), 1 + modded.weapons[130].fields().len());
+        assert_eq!(
+            changes[0].to_string(),
+            "arr\\units.dat 0 hit_points: 0 -> 10240"
+        );
+        assert_eq!(changes[1].to_string(), "arr\\weapons.dat 130 label: - -> 0");
+        assert!(changes
+            .iter()
+            .any(|change| change.field == "damage" && change.new.as_deref() == Some("5")));
+
+        fs.insert(orders::Orders::FILE_NAME, vec![0]);
+        assert!(vanilla.with_mod(&fs).is_err());
+
+        // an empty or truncated file fits no layout, even when its size is a multiple of
+        // an entry
+        let mut orders = Vec::new();
+        tables.orders.write(&mut orders).unwrap();
+        let entry_size = orders.len() / orders::Orders::COUNT;
+        for len in &[0, entry_size * 10] {
+            let buf = orders[..*len].to_vec();
+            assert!(orders::Orders::from_buffer(&mut Cursor::new(&buf)).is_err());
+        }
+        let truncated = orders::Orders(tables.orders.0[..10].to_vec());
+        assert!(truncated.write(&mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,66 @@
+dat! {
+    /// An entry of `orders.dat`: how a unit carries out an order.
+    OrderType, Orders, "arr\\orders.dat", 189 {
+        /// String of the order's name in `stat_txt.tbl`
+        label: u16,
+        use_weapon_targeting: u8,
+        secondary_order: u8,
+        non_subunit: u8,
+        subunit_inheritance: u8,
+        subunit_can_use: u8,
+        can_be_interrupted: u8,
+        unknown7: u8,
+        can_be_queued: u8,
+        disabling_keeps_order: u8,
+        can_be_obstructed: u8,
+        fleeable: u8,
+        requires_movable: u8,
+        /// Index into `weapons.dat` whose targeting the order uses
+        weapon: u8,
+        /// Index into `techdata.dat` whose energy the order costs
+        tech: u8,
+        /// Iscript animation the order plays
+        animation: u8,
+        /// Button icon highlighted while the order runs
+        highlight: u16,
+        requirements: u16,
+        /// Order shown to other players when the order is hidden from them
+        obscured_order: u8,
+    }
+}
+
+impl OrderType {
+    pub fn can_be_interrupted(&self) -> bool {
+        self.can_be_interrupted != 0
+    }
+
+    pub fn can_be_queued(&self) -> bool {
+        self.can_be_queued != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_orders_read_the_vanilla_layout() {
+        let mut orders = Orders(vec![OrderType::default(); Orders::COUNT]);
+        orders[10] = OrderType {
+            can_be_queued: 1,
+            animation: 2,
+            highlight: 0xFFFF,
+            obscured_order: 23,
+            ..OrderType::default()
+        };
+        let mut buf = Vec::new();
+        orders.write(&mut buf).unwrap();
+        assert_eq!(buf.len(), 4158);
+
+        let read = Orders::from_buffer(&mut Cursor::new(&buf)).unwrap();
+        assert_eq!(read, orders);
+        assert!(read[10].can_be_queued());
+        assert!(!read[10].can_be_interrupted());
+    }
+}
@@ -0,0 +1,69 @@
+use super::super::tbl::TBL;
+
+dat! {
+    /// An entry of `portdata.dat`: a set of portrait videos.
+    Portrait, Portraits, "arr\\portdata.dat", 220 {
+        /// String of the videos' common path in `portdata.tbl`, relative to `portrait\`
+        file: u32,
+        /// How often the idle videos change
+        smk_change: u8,
+        unknown1: u8,
+    }
+}
+
+impl Portrait {
+    /// Path of the `index`th video of the set, given `portdata.tbl`
+    pub fn smk_path(&self, portdata_tbl: &TBL, index: usize) -> Option<String> {
+        if self.file > u16::MAX as u32 {
+            return None;
+        }
+
+        portdata_tbl
+            .by_id(self.file as u16)
+            .map(|path| format!("portrait\\{}{}.smk", path, index))
+    }
+}
+
+impl Portraits {
+    /// The first half of the table holds idle sets, the second half talking sets
+    const TALKING_OFFSET: usize = Portraits::COUNT / 2;
+
+    /// The idle videos of portrait `id` from `units.dat`
+    pub fn idle(&self, id: u16) -> Option<&Portrait> {
+        self.0.get(id as usize)
+    }
+
+    /// The talking videos of portrait `id` from `units.dat`
+    pub fn talking(&self, id: u16) -> Option<&Portrait> {
+        self.0.get(id as usize + Portraits::TALKING_OFFSET)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_portraits_pair_idle_and_talking_sets() {
+        let mut portraits = Portraits(vec![Portrait::default(); Portraits::COUNT]);
+        portraits[0].file = 1;
+        portraits[110].file = 2;
+        let mut buf = Vec::new();
+        portraits.write(&mut buf).unwrap();
+        assert_eq!(buf.len(), 1320);
+        let portraits = Portraits::from_buffer(&mut Cursor::new(&buf)).unwrap();
+        assert_eq!(portraits.0.len(), Portraits::COUNT);
+
+        let tbl = TBL(vec!["tmarine\\TMaFid".into(), "tmarine\\TMaTlk0".into()]);
+        assert_eq!(
+            portraits.idle(0).unwrap().smk_path(&tbl, 2).as_deref(),
+            Some("portrait\\tmarine\\TMaFid2.smk")
+        );
+        assert_eq!(
+            portraits.talking(0).unwrap().smk_path(&tbl, 0).as_deref(),
+            Some("portrait\\tmarine\\TMaTlk00.smk")
+        );
+        assert!(portraits.talking(110).is_none());
+    }
+}
@@ -0,0 +1,57 @@
+use super::super::tbl::TBL;
+
+dat! {
+    /// An entry of `sfxdata.dat`: a sound effect and how it is played.
+    SoundEffect, SoundEffects, "arr\\sfxdata.dat", 1144 {
+        /// String of the sound's path in `sfxdata.tbl`, relative to `sound\`
+        file: u32,
+        unknown: u8,
+        flags: u8,
+        race: u16,
+        volume: u8,
+    }
+}
+
+impl SoundEffect {
+    /// Path of the sound, given `sfxdata.tbl`
+    pub fn path(&self, sfxdata_tbl: &TBL) -> Option<String> {
+        if self.file > u16::MAX as u32 {
+            return None;
+        }
+
+        sfxdata_tbl
+            .by_id(self.file as u16)
+            .map(|path| format!("sound\\{}", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_sound_effects_resolve_their_paths() {
+        let mut sounds = SoundEffects(vec![SoundEffect::default(); SoundEffects::COUNT]);
+        sounds[1] = SoundEffect {
+            file: 2,
+            race: 1,
+            volume: 100,
+            ..SoundEffect::default()
+        };
+        let mut buf = Vec::new();
+        sounds.write(&mut buf).unwrap();
+        assert_eq!(buf.len(), 10296);
+        let sounds = SoundEffects::from_buffer(&mut Cursor::new(&buf)).unwrap();
+
+        let tbl = TBL(vec![
+            "Zerg\\Drone\\ZDrErr00.WAV".into(),
+            "Misc\\Buzz.wav".into(),
+        ]);
+        assert_eq!(
+            sounds[1].path(&tbl).as_deref(),
+            Some("sound\\Misc\\Buzz.wav")
+        );
+        assert_eq!(sounds[0].path(&tbl), None);
+    }
+}
@@ -0,0 +1,30 @@
+dat! {
+    /// An entry of `sprites.dat`: the main image of a sprite and its selection graphics.
+    SpriteType, Sprites, "arr\\sprites.dat", 517 {
+        /// Index into `images.dat`
+        image: u16,
+        /// Width in pixels of the health bar; only sprites 130 to 516 can be selected
+        health_bar: u8 [130..517],
+        unknown: u8,
+        is_visible: u8,
+        /// Size of the selection circle, counting from `SpriteType::FIRST_SELECTION_CIRCLE`
+        selection_circle: u8 [130..517],
+        /// Vertical offset in pixels of the selection circle
+        selection_circle_offset: u8 [130..517],
+    }
+}
+
+impl SpriteType {
+    /// The `images.dat` entry of the smallest selection circle
+    pub const FIRST_SELECTION_CIRCLE: u16 = 561;
+
+    /// The `images.dat` entry of the selection circle
+    pub fn selection_circle_image(&self) -> u16 {
+        SpriteType::FIRST_SELECTION_CIRCLE + self.selection_circle as u16
+    }
+
+    /// Boxes in the health bar, each three pixels wide with a shared border
+    pub fn health_bar_boxes(&self) -> usize {
+        (self.health_bar as usize).saturating_sub(1) / 3
+    }
+}
@@ -0,0 +1,91 @@
+use super::super::chk::TechSettings;
+use super::super::map::Map;
+
+dat! {
+    /// An entry of `techdata.dat`: what researching and using a technology costs.
+    TechType, Techs, "arr\\techdata.dat", 44 {
+        mineral_cost: u16,
+        gas_cost: u16,
+        /// In frames
+        research_time: u16,
+        /// Energy each use takes, in 256ths
+        energy_cost: u16,
+        unknown: u32,
+        icon: u16,
+        /// String of the technology's name in `stat_txt.tbl`
+        label: u16,
+        race: u8,
+        unused: u8,
+        brood_war: u8,
+    }
+}
+
+impl TechType {
+    /// Whether the technology only exists in Brood War
+    pub fn is_brood_war(&self) -> bool {
+        self.brood_war != 0
+    }
+
+    /// Whole energy each use takes, as the game displays it
+    pub fn whole_energy_cost(&self) -> u16 {
+        self.energy_cost >> 8
+    }
+}
+
+impl Techs {
+    /// Applies the technologies a map's settings override
+    pub fn apply(&mut self, settings: &TechSettings) {
+        for (i, tech) in self.0.iter_mut().enumerate() {
+            if settings.use_defaults.get(i).copied().unwrap_or(true) {
+                continue;
+            }
+
+            tech.mineral_cost = settings.mineral_cost[i];
+            tech.gas_cost = settings.gas_cost[i];
+            tech.research_time = settings.research_time[i];
+            tech.energy_cost = settings.energy_cost[i];
+        }
+    }
+
+    /// The table as it is in effect on `map`
+    pub fn for_map(&self, map: &Map) -> Techs {
+        let mut techs = self.clone();
+        if let Some(settings) = &map.tech_settings {
+            techs.apply(settings);
+        }
+
+        techs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_techs_apply_settings_that_do_not_use_defaults() {
+        let mut techs = Techs(vec![TechType::default(); Techs::COUNT]);
+        techs[0].energy_cost = 100 << 8;
+        let mut buf = Vec::new();
+        techs.write(&mut buf).unwrap();
+        assert_eq!(buf.len(), 836);
+        let mut techs = Techs::from_buffer(&mut Cursor::new(&buf)).unwrap();
+        assert_eq!(techs[0].whole_energy_cost(), 100);
+
+        let mut settings = TechSettings {
+            use_defaults: vec![true; Techs::COUNT],
+            mineral_cost: vec![0; Techs::COUNT],
+            gas_cost: vec![0; Techs::COUNT],
+            research_time: vec![0; Techs::COUNT],
+            energy_cost: vec![0; Techs::COUNT],
+        };
+        settings.use_defaults[0] = false;
+        settings.energy_cost[0] = 50 << 8;
+        settings.energy_cost[1] = 75 << 8;
+
+        techs.apply(&settings);
+        assert_eq!(techs[0].whole_energy_cost(), 50);
+        assert_eq!(techs[1].energy_cost, 0);
+    }
+}
@@ -0,0 +1,234 @@
+use super::super::chk::UnitSettings;
+use super::super::map::Map;
+use super::super::placement::UnitFootprints;
+use super::{Bounds, Size};
+
+dat! {
+    /// An entry of `units.dat`: how a unit, building or other entity is drawn, fights,
+    /// costs and behaves.
+    UnitType, Units, "arr\\units.dat", 228 {
+        /// Index into `flingy.dat`
+        flingy: u8,
+        /// A turret's unit type, or 228 for none
+        subunit1: u16,
+        subunit2: u16,
+        /// What a Terran building becomes when infested, for buildings 106 to 201
+        infestation: u16 [106..202],
+        /// Index into `images.dat` of the construction graphic
+        construction_image: u32,
+        /// Direction the unit faces when created, where 32 is random
+        direction: u8,
+        shields_enabled: u8,
+        shields: u16,
+        /// In 256ths of a hit point
+        hit_points: u32,
+        elevation: u8,
+        movement_flags: u8,
+        rank: u8,
+        /// Orders from `orders.dat`
+        computer_idle_order: u8,
+        human_idle_order: u8,
+        return_to_idle_order: u8,
+        attack_unit_order: u8,
+        attack_move_order: u8,
+        /// Index into `weapons.dat`, or 130 for none
+        ground_weapon: u8,
+        max_ground_hits: u8,
+        air_weapon: u8,
+        max_air_hits: u8,
+        ai_flags: u8,
+        special_flags: u32,
+        /// In tiles
+        target_acquisition_range: u8,
+        /// In tiles
+        sight_range: u8,
+        /// Index into `upgrades.dat`
+        armor_upgrade: u8,
+        size: u8,
+        armor: u8,
+        right_click_action: u8,
+        /// Indices into `sfxdata.dat`; only units 0 to 105 speak
+        ready_sound: u16 [0..106],
+        first_what_sound: u16,
+        last_what_sound: u16,
+        first_annoyed_sound: u16 [0..106],
+        last_annoyed_sound: u16 [0..106],
+        first_yes_sound: u16 [0..106],
+        last_yes_sound: u16 [0..106],
+        /// In pixels
+        placement_box: Size,
+        /// Position of an addon relative to its parent building, for buildings 106 to 201
+        addon_position: Size [106..202],
+        dimensions: Bounds,
+        /// Index into `portdata.dat`
+        portrait: u16,
+        mineral_cost: u16,
+        gas_cost: u16,
+        /// In frames
+        build_time: u16,
+        requirements: u16,
+        group_flags: u8,
+        /// Doubled, so that zerglings can take half of a supply
+        supply_provided: u8,
+        supply_required: u8,
+        space_required: u8,
+        space_provided: u8,
+        build_score: u16,
+        destroy_score: u16,
+        /// String of the name in the map's STR section, or 0 for the default
+        name_string: u16,
+        brood_war: u8,
+        availability_flags: u16,
+    }
+}
+
+impl UnitType {
+    pub const BUILDING: u32 = 0x1;
+    pub const ADDON: u32 = 0x2;
+    pub const FLYER: u32 = 0x4;
+    pub const WORKER: u32 = 0x8;
+    pub const SUBUNIT: u32 = 0x10;
+    pub const HERO: u32 = 0x40;
+    pub const RESOURCE_DEPOT: u32 = 0x1000;
+    pub const RESOURCE_CONTAINER: u32 = 0x2000;
+    pub const DETECTOR: u32 = 0x8000;
+    pub const REQUIRES_CREEP: u32 = 0x20000;
+    pub const REQUIRES_PSI: u32 = 0x80000;
+    pub const SPELLCASTER: u32 = 0x200000;
+
+    pub const ZERG: u8 = 0x1;
+    pub const TERRAN: u8 = 0x2;
+    pub const PROTOSS: u8 = 0x4;
+
+    pub fn has_flag(&self, flag: u32) -> bool {
+        self.special_flags & flag != 0
+    }
+
+    /// Whether the unit only exists in Brood War
+    pub fn is_brood_war(&self) -> bool {
+        self.brood_war != 0
+    }
+
+    pub fn has_shields(&self) -> bool {
+        self.shields_enabled != 0
+    }
+
+    /// Whole hit points, as the game displays them
+    pub fn whole_hit_points(&self) -> u32 {
+        self.hit_points >> 8
+    }
+
+    /// Width and height in tiles of the placement box, rounded up
+    pub fn footprint(&self) -> (usize, usize) {
+        (
+            (self.placement_box.width as usize).div_ceil(32),
+            (self.placement_box.height as usize).div_ceil(32),
+        )
+    }
+}
+
+impl Units {
+    /// The entries that only exist in Brood War
+    pub fn brood_war(&self) -> impl Iterator<Item = (usize, &UnitType)> {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|(_, unit)| unit.is_brood_war())
+    }
+
+    /// Applies the unit stats a map's settings override
+    pub fn apply(&mut self, settings: &UnitSettings) {
+        for (i, unit) in self.0.iter_mut().enumerate() {
+            if settings.use_defaults.get(i).copied().unwrap_or(true) {
+                continue;
+            }
+
+            unit.hit_points = settings.hit_points[i];
+            unit.shields = settings.shields[i];
+            unit.armor = settings.armor[i];
+            unit.build_time = settings.build_time[i];
+            unit.mineral_cost = settings.mineral_cost[i];
+            unit.gas_cost = settings.gas_cost[i];
+            unit.name_string = settings.name_string[i];
+        }
+    }
+
+    /// The table as it is in effect on `map`
+    pub fn for_map(&self, map: &Map) -> Units {
+        let mut units = self.clone();
+        if let Some(settings) = &map.unit_settings {
+            units.apply(settings);
+        }
+
+        units
+    }
+}
+
+/// Buildings and resources block the tiles of their placement box.
+impl UnitFootprints for Units {
+    fn footprint(&self, unit_id: u16) -> Option<(usize, usize)> {
+        self.0
+            .get(unit_id as usize)
+            .filter(|unit| {
+                unit.has_flag(UnitType::BUILDING) || unit.has_flag(UnitType::RESOURCE_CONTAINER)
+            })
+            .map(UnitType::footprint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_units_read_both_layouts() {
+        let mut units = Units(vec![UnitType::default(); Units::COUNT]);
+        units[156] = UnitType {
+            hit_points: 300 << 8,
+            shields_enabled: 1,
+            placement_box: Size {
+                width: 64,
+                height: 64,
+            },
+            special_flags: UnitType::BUILDING,
+            infestation: 7,
+            addon_position: Size {
+                width: 3,
+                height: 4,
+            },
+            ..UnitType::default()
+        };
+        units[201].brood_war = 1;
+        // units 0 to 105 have no infestation column, so it is dropped
+        units[0].infestation = 9;
+
+        let mut buf = Vec::new();
+        units.write(&mut buf).unwrap();
+        assert_eq!(buf.len(), 19876);
+
+        let mut cursor = Cursor::new(&buf);
+        let read = Units::from_buffer(&mut cursor).unwrap();
+        assert_eq!(cursor.position(), buf.len() as u64);
+        assert_eq!(read[156], units[156]);
+        assert_eq!(read[0].infestation, 0);
+        assert_eq!(read[156].whole_hit_points(), 300);
+        assert_eq!(read.footprint(156), Some((2, 2)));
+        assert_eq!(read.footprint(0), None);
+        assert_eq!(
+            read.brood_war().map(|(id, _)| id).collect::<Vec<_>>(),
+            vec![201]
+        );
+
+        // an extended file with an extra unit keeps every column for every unit
+        units.0.push(units[156].clone());
+        units[0].infestation = 9;
+        let mut buf = Vec::new();
+        units.write(&mut buf).unwrap();
+        let read = Units::from_buffer(&mut Cursor::new(&buf)).unwrap();
+        assert_eq!(read, units);
+
+        buf.pop();
+        assert!(Units::from_buffer(&mut Cursor::new(&buf)).is_err());
+    }
+}
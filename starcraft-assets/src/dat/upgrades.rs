@@ -0,0 +1,103 @@
+use super::super::chk::UpgradeSettings;
+use super::super::map::Map;
+
+dat! {
+    /// An entry of `upgrades.dat`: what researching each level of an upgrade costs.
+    UpgradeType, Upgrades, "arr\\upgrades.dat", 61 {
+        mineral_cost: u16,
+        /// Minerals added for each level after the first
+        mineral_factor: u16,
+        gas_cost: u16,
+        gas_factor: u16,
+        /// In frames
+        research_time: u16,
+        research_time_factor: u16,
+        unknown: u16,
+        icon: u16,
+        /// String of the upgrade's name in `stat_txt.tbl`
+        label: u16,
+        race: u8,
+        max_level: u8,
+        brood_war: u8,
+    }
+}
+
+impl UpgradeType {
+    /// Whether the upgrade only exists in Brood War
+    pub fn is_brood_war(&self) -> bool {
+        self.brood_war != 0
+    }
+
+    /// Minerals, gas and frames that researching level `level` takes, counting from 1
+    pub fn cost(&self, level: u8) -> (u32, u32, u32) {
+        let steps = level.saturating_sub(1) as u32;
+        (
+            self.mineral_cost as u32 + self.mineral_factor as u32 * steps,
+            self.gas_cost as u32 + self.gas_factor as u32 * steps,
+            self.research_time as u32 + self.research_time_factor as u32 * steps,
+        )
+    }
+}
+
+impl Upgrades {
+    /// Applies the upgrades a map's settings override
+    pub fn apply(&mut self, settings: &UpgradeSettings) {
+        for (i, upgrade) in self.0.iter_mut().enumerate() {
+            if settings.use_defaults.get(i).copied().unwrap_or(true) {
+                continue;
+            }
+
+            upgrade.mineral_cost = settings.mineral_cost[i];
+            upgrade.mineral_factor = settings.mineral_factor[i];
+            upgrade.gas_cost = settings.gas_cost[i];
+            upgrade.gas_factor = settings.gas_factor[i];
+            upgrade.research_time = settings.research_time[i];
+            upgrade.research_time_factor = settings.research_time_factor[i];
+        }
+    }
+
+    /// The table as it is in effect on `map`
+    pub fn for_map(&self, map: &Map) -> Upgrades {
+        let mut upgrades = self.clone();
+        if let Some(settings) = &map.upgrade_settings {
+            upgrades.apply(settings);
+        }
+
+        upgrades
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_upgrades_apply_settings_that_do_not_use_defaults() {
+        let mut upgrades = Upgrades(vec![UpgradeType::default(); Upgrades::COUNT]);
+        upgrades[0] = UpgradeType {
+            mineral_cost: 100,
+            mineral_factor: 75,
+            max_level: 3,
+            ..UpgradeType::default()
+        };
+        let mut buf = Vec::new();
+        upgrades.write(&mut buf).unwrap();
+        assert_eq!(buf.len(), 1281);
+        let mut upgrades = Upgrades::from_buffer(&mut Cursor::new(&buf)).unwrap();
+        assert_eq!(upgrades[0].cost(3).0, 250);
+
+        let count = Upgrades::COUNT;
+        let mut section = vec![1u8; count];
+        section[1] = 0;
+        // padding, then a column of mineral costs and five of zeros
+        section.push(0);
+        section.extend((0..count as u16).flat_map(|i| (i * 10).to_le_bytes().to_vec()));
+        section.resize(UpgradeSettings::size(true), 0);
+        let settings = UpgradeSettings::from_buffer(&mut Cursor::new(&section), true).unwrap();
+
+        upgrades.apply(&settings);
+        assert_eq!(upgrades[0].mineral_cost, 100);
+        assert_eq!(upgrades[1].cost(1), (10, 0, 0));
+    }
+}
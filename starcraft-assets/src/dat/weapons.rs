@@ -0,0 +1,131 @@
+use super::super::chk::UnitSettings;
+use super::super::map::Map;
+
+dat! {
+    /// An entry of `weapons.dat`: damage, range and the graphics of an attack.
+    WeaponType, Weapons, "arr\\weapons.dat", 130 {
+        /// String of the weapon's name in `stat_txt.tbl`
+        label: u16,
+        /// Index into `flingy.dat` of the projectile or effect
+        graphics: u32,
+        unused: u8,
+        target_flags: u16,
+        /// In pixels
+        min_range: u32,
+        /// In pixels
+        max_range: u32,
+        /// Index into `upgrades.dat` of the upgrade that adds `damage_bonus`
+        damage_upgrade: u8,
+        /// Independent, explosive, concussive, normal or ignore armor
+        damage_type: u8,
+        /// How the graphics move, such as a homing projectile or a bounce
+        behavior: u8,
+        /// Frames before the graphics are removed
+        remove_after: u8,
+        /// What the weapon does on hit, such as splash or a spell effect
+        explosion_type: u8,
+        /// Radii in pixels of full, half and quarter splash damage
+        inner_splash: u16,
+        medium_splash: u16,
+        outer_splash: u16,
+        damage: u16,
+        /// Damage each level of `damage_upgrade` adds
+        damage_bonus: u16,
+        /// In frames
+        cooldown: u8,
+        /// Times the damage is dealt, such as two for the Goliath's missiles
+        damage_factor: u8,
+        attack_angle: u8,
+        launch_spin: u8,
+        forward_offset: u8,
+        upward_offset: u8,
+        /// String shown when the target is invalid
+        target_error_message: u16,
+        icon: u16,
+    }
+}
+
+impl WeaponType {
+    pub const AIR: u16 = 0x1;
+    pub const GROUND: u16 = 0x2;
+    pub const MECHANICAL: u16 = 0x4;
+    pub const ORGANIC: u16 = 0x8;
+    pub const NON_BUILDING: u16 = 0x10;
+    pub const NON_ROBOTIC: u16 = 0x20;
+    pub const TERRAIN: u16 = 0x40;
+    pub const ORGANIC_OR_MECHANICAL: u16 = 0x80;
+    pub const OWN: u16 = 0x100;
+
+    pub fn can_target(&self, flag: u16) -> bool {
+        self.target_flags & flag != 0
+    }
+
+    /// Damage at upgrade level `level`, before armor
+    pub fn damage_at(&self, level: u8) -> u32 {
+        (self.damage as u32 + self.damage_bonus as u32 * level as u32) * self.damage_factor as u32
+    }
+}
+
+impl Weapons {
+    /// Applies the weapon damage of a map's unit settings
+    pub fn apply(&mut self, settings: &UnitSettings) {
+        let overrides = settings.weapon_damage.iter().zip(&settings.weapon_bonus);
+        for (weapon, (damage, bonus)) in self.0.iter_mut().zip(overrides) {
+            weapon.damage = *damage;
+            weapon.damage_bonus = *bonus;
+        }
+    }
+
+    /// The table as it is in effect on `map`
+    pub fn for_map(&self, map: &Map) -> Weapons {
+        let mut weapons = self.clone();
+        if let Some(settings) = &map.unit_settings {
+            weapons.apply(settings);
+        }
+
+        weapons
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::chk::{Chunk, Header};
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_weapons_take_damage_from_unit_settings() {
+        let mut weapons = Weapons(vec![WeaponType::default(); Weapons::COUNT]);
+        weapons[0] = WeaponType {
+            damage: 6,
+            damage_bonus: 1,
+            damage_factor: 1,
+            max_range: 128,
+            target_flags: WeaponType::AIR | WeaponType::GROUND,
+            ..WeaponType::default()
+        };
+        let mut buf = Vec::new();
+        weapons.write(&mut buf).unwrap();
+        assert_eq!(buf.len(), 5460);
+        let mut weapons = Weapons::from_buffer(&mut Cursor::new(&buf)).unwrap();
+        assert_eq!(weapons[0].max_range, 128);
+        assert!(weapons[0].can_target(WeaponType::AIR));
+        assert_eq!(weapons[0].damage_at(3), 9);
+
+        // a UNIx section that changes the first weapon to 20 damage and 2 per upgrade
+        let mut section = vec![0u8; UnitSettings::size(true)];
+        let weapon_damage = 228 * 16;
+        section[weapon_damage..weapon_damage + 2].copy_from_slice(&20u16.to_le_bytes());
+        let weapon_bonus = weapon_damage + 130 * 2;
+        section[weapon_bonus..weapon_bonus + 2].copy_from_slice(&2u16.to_le_bytes());
+        let header = Header::new(*b"UNIx", section.len()).unwrap();
+        let settings = match Chunk::read(&header, &mut Cursor::new(&section)).unwrap() {
+            Some(Chunk::BroodWarUnitSettings(settings)) => settings,
+            _ => panic!("expected Brood War unit settings"),
+        };
+
+        weapons.apply(&settings);
+        assert_eq!(weapons[0].damage_at(3), 26);
+        assert_eq!(weapons[1].damage, 0);
+    }
+}
@@ -0,0 +1,141 @@
+use super::errors::*;
+use super::fs::ReadSeek;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A read-only file system over a folder of extracted game data.
+///
+/// MPQ style paths such as `tileset\jungle.cv5` are resolved relative to the root,
+/// matching each path component case-insensitively like the game does.
+pub struct DirectoryFileSystem {
+    root: PathBuf,
+}
+
+impl DirectoryFileSystem {
+    pub fn new(root: impl Into<PathBuf>) -> DirectoryFileSystem {
+        DirectoryFileSystem { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Finds the host path of `file_name`, if it exists
+    pub fn resolve(&self, file_name: &str) -> Option<PathBuf> {
+        let mut path = self.root.clone();
+        for component in file_name.split(&['\\', '/'][..]) {
+            if component.is_empty() || component == "." {
+                continue;
+            }
+            if component == ".." {
+                return None;
+            }
+
+            let exact = path.join(component);
+            if exact.exists() {
+                path = exact;
+                continue;
+            }
+
+            let entry = fs::read_dir(&path).ok()?.flatten().find(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .eq_ignore_ascii_case(component)
+            })?;
+            path = entry.path();
+        }
+
+        Some(path)
+    }
+
+    fn collect_files(&self, dir: &Path, prefix: &str, files: &mut Vec<String>) -> Result<()> {
+        let entries = fs::read_dir(dir)
+            .chain_err(|| ErrorKind::Io(prefix.into(), self.root.display().to_string(), None))?;
+        for entry in entries.flatten() {
+            let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+            if entry.path().is_dir() {
+                self.collect_files(&entry.path(), &format!("{}\\", name), files)?;
+            } else {
+                files.push(name);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl super::fs::ReadOnlyFileSystem for DirectoryFileSystem {
+    fn read(&self, file_name: &str) -> Result<Vec<u8>> {
+        let path = self
+            .resolve(file_name)
+            .filter(|path| path.is_file())
+            .ok_or_else(|| {
+                ErrorKind::NotFound(file_name.into(), self.root.display().to_string())
+            })?;
+
+        fs::read(&path)
+            .chain_err(|| ErrorKind::Io(file_name.into(), self.root.display().to_string(), None))
+    }
+
+    fn open(&self, file_name: &str) -> Result<Box<dyn ReadSeek + '_>> {
+        let path = self
+            .resolve(file_name)
+            .filter(|path| path.is_file())
+            .ok_or_else(|| {
+                ErrorKind::NotFound(file_name.into(), self.root.display().to_string())
+            })?;
+
+        let file = fs::File::open(&path)
+            .chain_err(|| ErrorKind::Io(file_name.into(), self.root.display().to_string(), None))?;
+        Ok(Box::new(file))
+    }
+
+    fn files(&self) -> Result<Vec<String>> {
+        let mut files = Vec::new();
+        self.collect_files(&self.root, "", &mut files)?;
+        files.sort_by_key(|file_name| file_name.to_ascii_lowercase());
+
+        Ok(files)
+    }
+
+    fn exists(&self, file_name: &str) -> Result<bool> {
+        Ok(self
+            .resolve(file_name)
+            .filter(|path| path.is_file())
+            .is_some())
+    }
+
+    fn size(&self, file_name: &str) -> Result<u64> {
+        self.resolve(file_name)
+            .and_then(|path| fs::metadata(path).ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .ok_or_else(|| {
+                ErrorKind::NotFound(file_name.into(), self.root.display().to_string()).into()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fs::ReadOnlyFileSystem;
+    use super::*;
+
+    #[test]
+    fn test_directory_file_system_matches_mpq_paths_case_insensitively() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir(root.path().join("TileSet")).unwrap();
+        fs::write(root.path().join("TileSet").join("Jungle.CV5"), [1, 2, 3]).unwrap();
+        let fs = DirectoryFileSystem::new(root.path());
+
+        assert_eq!(fs.read("tileset\\jungle.cv5").unwrap(), vec![1, 2, 3]);
+        assert!(fs.read("tileset\\badlands.cv5").unwrap_err().is_not_found());
+        assert!(fs.read("tileset").is_err());
+        assert!(fs.read("..\\tileset\\jungle.cv5").is_err());
+        assert_eq!(fs.files().unwrap(), vec!["TileSet\\Jungle.CV5"]);
+        assert_eq!(fs.glob("tileset\\*.cv5").unwrap().len(), 1);
+        assert_eq!(fs.size("tileset\\jungle.cv5").unwrap(), 3);
+        assert!(!fs.exists("tileset").unwrap());
+    }
+}
@@ -0,0 +1,317 @@
+//! Small synthetic tilesets and maps, so loading and rendering can be tested without
+//! the game's archives.
+//!
+//! The data is deterministic but meaningless: every tile group, MegaTile and MiniTile
+//! is distinct so tests can tell which one was drawn.
+
+use super::chk;
+use super::cv5::{self, CV5s};
+use super::loader::Tileset;
+use super::memory::MemoryFileSystem;
+
+/// Tile groups in a synthetic CV5
+pub const GROUP_COUNT: usize = 4;
+/// MegaTiles in a synthetic VF4 and VX4
+pub const MEGA_TILE_COUNT: usize = 8;
+/// MiniTiles in a synthetic VR4
+pub const MINI_TILE_COUNT: usize = 32;
+
+const CV5_BLOCK_SIZE: usize = 52;
+const SUBTILE_COUNT: usize = 16;
+
+/// The MegaTile that subtile `subtile` of tile group `group` refers to
+pub fn mega_tile(group: usize, subtile: usize) -> usize {
+    (group * SUBTILE_COUNT + subtile) % MEGA_TILE_COUNT
+}
+
+/// The MiniTile drawn at position `position` of MegaTile `mega_tile`, and whether it is
+/// flipped horizontally
+pub fn mini_tile(mega_tile: usize, position: usize) -> (usize, bool) {
+    (
+        (mega_tile * 16 + position) % MINI_TILE_COUNT,
+        position % 2 == 1,
+    )
+}
+
+/// The palette index of pixel `pixel` of MiniTile `mini_tile`, before any flip
+pub fn color_index(mini_tile: usize, pixel: usize) -> u8 {
+    (mini_tile * 8 + pixel) as u8
+}
+
+pub fn cv5() -> Vec<u8> {
+    let mut buf = Vec::with_capacity(GROUP_COUNT * CV5_BLOCK_SIZE);
+    for group in 0..GROUP_COUNT {
+        // group type, flags and the edge or doodad data
+        buf.extend_from_slice(&[0u8; 20]);
+        push_mega_tiles(&mut buf, group);
+    }
+
+    buf
+}
+
+/// The synthetic CV5, padded with empty tile groups up to `CV5s::DOODAD_GROUP_START`,
+/// followed by the tile groups of `doodad`, one per row
+pub fn cv5_with_doodad(doodad: &cv5::Doodad) -> Vec<u8> {
+    let mut buf = cv5();
+    buf.resize(CV5s::DOODAD_GROUP_START * CV5_BLOCK_SIZE, 0);
+    for row in 0..doodad.height as usize {
+        let data = [
+            doodad.overlay_id,
+            doodad.overlay_flags,
+            doodad.string_index,
+            0,
+            doodad.dddata_index,
+            doodad.width,
+            doodad.height,
+            0,
+        ];
+        // group type and flags
+        buf.extend_from_slice(&[0u8; 4]);
+        data.iter()
+            .for_each(|value| buf.extend_from_slice(&value.to_le_bytes()));
+        push_mega_tiles(&mut buf, CV5s::DOODAD_GROUP_START + row);
+    }
+
+    buf
+}
+
+fn push_mega_tiles(buf: &mut Vec<u8>, group: usize) {
+    for subtile in 0..SUBTILE_COUNT {
+        buf.extend_from_slice(&(mega_tile(group, subtile) as u16).to_le_bytes());
+    }
+}
+
+pub fn vf4() -> Vec<u8> {
+    // every MiniTile walkable and nothing else
+    (0..MEGA_TILE_COUNT * 16)
+        .flat_map(|_| 1u16.to_le_bytes().to_vec())
+        .collect()
+}
+
+pub fn vx4() -> Vec<u8> {
+    let mut buf = Vec::with_capacity(MEGA_TILE_COUNT * 32);
+    for mega_tile in 0..MEGA_TILE_COUNT {
+        for position in 0..16 {
+            let (mini_tile, flipped) = mini_tile(mega_tile, position);
+            buf.extend_from_slice(&((mini_tile << 1 | flipped as usize) as u16).to_le_bytes());
+        }
+    }
+
+    buf
+}
+
+pub fn vr4() -> Vec<u8> {
+    (0..MINI_TILE_COUNT)
+        .flat_map(|mini_tile| (0..64).map(move |pixel| color_index(mini_tile, pixel)))
+        .collect()
+}
+
+/// A grayscale palette, so each palette index maps to a distinct color
+pub fn wpe() -> Vec<u8> {
+    (0..=255u8).flat_map(|i| vec![i, i, i, 0]).collect()
+}
+
+/// A GRP with a single frame covering its `width`x`height` bounds, stored as one literal
+/// run per row
+pub fn grp(width: u8, height: u8, pixels: &[u8]) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let header = [1, width as u16, height as u16];
+    let mut buf = header
+        .iter()
+        .flat_map(|value| value.to_le_bytes().to_vec())
+        .collect::<Vec<_>>();
+
+    let offset = buf.len() + 8;
+    buf.extend_from_slice(&[0, 0, width as u8, height as u8]);
+    buf.extend_from_slice(&(offset as u32).to_le_bytes());
+    for y in 0..height {
+        let line_offset = height * 2 + y * (width + 1);
+        buf.extend_from_slice(&(line_offset as u16).to_le_bytes());
+    }
+    for row in pixels.chunks(width.max(1)).take(height) {
+        buf.push(width as u8);
+        buf.extend_from_slice(row);
+    }
+
+    buf
+}
+
+/// Adds the five files of a synthetic `tileset` to `fs`, where `AssetLoader` looks for them
+pub fn insert_tileset(fs: &mut MemoryFileSystem, tileset: Tileset) {
+    let name = tileset.file_name();
+    fs.insert(format!("tileset\\{}.cv5", name), cv5());
+    fs.insert(format!("tileset\\{}.vf4", name), vf4());
+    fs.insert(format!("tileset\\{}.vx4", name), vx4());
+    fs.insert(format!("tileset\\{}.vr4", name), vr4());
+    fs.insert(format!("tileset\\{}.wpe", name), wpe());
+}
+
+/// The raw MTXM value of the tile at column `x` and row `y` of a synthetic map
+pub fn mega_tile_id(x: usize, y: usize) -> u16 {
+    let group = (x + y) % GROUP_COUNT;
+    let subtile = (x * 3 + y) % SUBTILE_COUNT;
+
+    (group << 4 | subtile) as u16
+}
+
+/// A Brood War scenario of `width`x`height` tiles with every required section,
+/// `doodads` placed in DD2 and `sprites` placed in THG2
+pub fn chk(
+    tileset: chk::Tileset,
+    width: u16,
+    height: u16,
+    doodads: &[chk::Doodad],
+    sprites: &[chk::Sprite],
+) -> Vec<u8> {
+    let mut buf = Vec::new();
+    let mut section = |name: &[u8; 4], data: &[u8]| {
+        buf.extend_from_slice(name);
+        buf.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        buf.extend_from_slice(data);
+    };
+
+    section(b"TYPE", b"RAWB");
+    section(b"VER ", &205u16.to_le_bytes());
+    section(b"ERA ", &(tileset as u16).to_le_bytes());
+    // one human player against computers, the rest unused
+    let mut controllers = [0u8; 12];
+    controllers[..8].copy_from_slice(&[6, 5, 5, 5, 5, 5, 5, 5]);
+    section(b"OWNR", &controllers);
+    let dimensions = [width.to_le_bytes(), height.to_le_bytes()].concat();
+    section(b"DIM ", &dimensions);
+    let mut sides = [7u8; 12];
+    sides[..8].copy_from_slice(&[5; 8]);
+    section(b"SIDE", &sides);
+
+    let mega_tile_ids = (0..height as usize)
+        .flat_map(|y| (0..width as usize).map(move |x| mega_tile_id(x, y)))
+        .flat_map(|id| id.to_le_bytes().to_vec())
+        .collect::<Vec<_>>();
+    section(b"MTXM", &mega_tile_ids);
+
+    let strings = [&1u16.to_le_bytes()[..], &4u16.to_le_bytes(), b"Synthetic\0"].concat();
+    section(b"STR ", &strings);
+
+    let doodads = doodads
+        .iter()
+        .flat_map(|doodad| {
+            [
+                &doodad.id.to_le_bytes()[..],
+                &doodad.x.to_le_bytes(),
+                &doodad.y.to_le_bytes(),
+                &[doodad.owner, doodad.disabled as u8],
+            ]
+            .concat()
+        })
+        .collect::<Vec<_>>();
+    section(b"DD2 ", &doodads);
+
+    let sprites = sprites
+        .iter()
+        .flat_map(|sprite| {
+            let mut flags = 0u16;
+            if sprite.is_pure_sprite() {
+                flags |= chk::Sprite::DRAW_AS_SPRITE;
+            }
+            if sprite.is_disabled() {
+                flags |= chk::Sprite::DISABLED;
+            }
+            [
+                &sprite.id.to_le_bytes()[..],
+                &sprite.x.to_le_bytes(),
+                &sprite.y.to_le_bytes(),
+                &[sprite.owner, 0],
+                &flags.to_le_bytes(),
+            ]
+            .concat()
+        })
+        .collect::<Vec<_>>();
+    section(b"THG2", &sprites);
+
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::loader::AssetLoader;
+    use super::super::map::Map;
+    use super::super::Assets;
+    use super::*;
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_synthetic_tileset_and_map_load_through_the_asset_pipeline() {
+        let mut fs = MemoryFileSystem::new("fixtures");
+        insert_tileset(&mut fs, Tileset::Jungle);
+
+        let doodad = chk::Doodad {
+            id: 3,
+            x: 48,
+            y: 16,
+            owner: 0,
+            disabled: false,
+        };
+        let overlay = chk::Sprite::new(5, 48, 16, 0, true);
+        let other = chk::Sprite::new(6, 8, 8, 0, true);
+        let map = Map::from_chunks(&chk(
+            chk::Tileset::Jungle,
+            3,
+            2,
+            &[doodad],
+            &[overlay.clone(), other.clone()],
+        ))
+        .unwrap();
+        assert_eq!(
+            map.dimensions,
+            chk::Dimensions {
+                width: 3,
+                height: 2
+            }
+        );
+        assert_eq!(map.tileset, chk::Tileset::Jungle);
+
+        let loader = AssetLoader::new(Tileset::from(map.tileset.clone()), &fs);
+        let assets = Assets::from(Arc::new(loader)).unwrap();
+        assert_eq!(assets.cv5s.0.len(), GROUP_COUNT);
+        assert_eq!(assets.vx4s.0.len(), MEGA_TILE_COUNT);
+        assert_eq!(assets.vr4s.0.len(), MINI_TILE_COUNT);
+        assert_eq!(assets.wpes.0.len(), 256);
+
+        let tile = &map.mega_tile_ids[1 + 3];
+        let mega_tile_index = assets.cv5s[tile.group_index()][tile.subtile_index()] as usize;
+        // the tile at (1, 1) is subtile 4 of group 2
+        assert_eq!(mega_tile_index, mega_tile(2, 4));
+        let minitile = assets.vx4s[mega_tile_index][5];
+        assert_eq!(
+            (minitile.index(), minitile.is_horizontally_flipped()),
+            mini_tile(mega_tile_index, 5)
+        );
+        assert_eq!(
+            assets.vr4s[minitile.index()][9],
+            color_index(minitile.index(), 9)
+        );
+
+        // the doodad's overlay is drawn once, mirrored as its tile group says
+        let cv5s = CV5s::from_buffer(&mut Cursor::new(&cv5_with_doodad(&cv5::Doodad {
+            overlay_id: 5,
+            // a sprite, mirrored
+            overlay_flags: 0x5,
+            string_index: 0,
+            dddata_index: 3,
+            width: 2,
+            height: 1,
+        })))
+        .unwrap();
+        assert_eq!(cv5s.find_doodad(3).unwrap().0, CV5s::DOODAD_GROUP_START);
+        let overlays = map.overlays(&cv5s);
+        assert_eq!(
+            overlays
+                .iter()
+                .map(|overlay| (overlay.sprite.clone(), overlay.flipped))
+                .collect::<Vec<_>>(),
+            vec![(overlay, true), (other, false)]
+        );
+        assert_eq!(map.overlays(&assets.cv5s).len(), 2);
+    }
+}
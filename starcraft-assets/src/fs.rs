@@ -1,5 +1,114 @@
 use super::errors::*;
+use std::io::{Cursor, Read, Seek};
+
+/// A file opened for reading, see `ReadOnlyFileSystem::open`
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
 
 pub trait ReadOnlyFileSystem: Send + Sync {
     fn read(&self, file_name: &str) -> Result<Vec<u8>>;
+
+    /// Opens a file to be read incrementally, such as a video or sound too large to
+    /// hold in memory. File systems that cannot stream a file read it whole.
+    fn open(&self, file_name: &str) -> Result<Box<dyn ReadSeek + '_>> {
+        Ok(Box::new(Cursor::new(self.read(file_name)?)))
+    }
+
+    /// Names of every file that is known to exist, sorted
+    ///
+    /// File systems that cannot enumerate their contents return an empty list.
+    fn files(&self) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// Names of the files matching `pattern`, see `glob_matches`
+    fn glob(&self, pattern: &str) -> Result<Vec<String>> {
+        Ok(self
+            .files()?
+            .into_iter()
+            .filter(|file_name| glob_matches(pattern, file_name))
+            .collect())
+    }
+
+    /// Whether a file exists, answered from metadata without reading the file
+    fn exists(&self, file_name: &str) -> Result<bool>;
+
+    /// Uncompressed size of a file in bytes, answered from metadata without reading
+    /// the file
+    fn size(&self, file_name: &str) -> Result<u64>;
+}
+
+/// Matches an MPQ path against a pattern such as `unit\zerg\*.grp`.
+///
+/// `*` matches any run of characters within a path component and `?` matches a single
+/// character. Matching ignores ASCII case and treats `/` like `\`.
+pub fn glob_matches(pattern: &str, file_name: &str) -> bool {
+    fn normalize(c: u8) -> u8 {
+        match c {
+            b'/' => b'\\',
+            c => c.to_ascii_lowercase(),
+        }
+    }
+
+    let pattern = pattern.as_bytes();
+    let file_name = file_name.as_bytes();
+    let (mut p, mut f) = (0, 0);
+    // position of the last `*` in the pattern and the file name position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while f < file_name.len() {
+        match pattern.get(p).map(|c| normalize(*c)) {
+            Some(b'*') => {
+                backtrack = Some((p, f));
+                p += 1;
+            }
+            Some(b'?') if file_name[f] != b'\\' && file_name[f] != b'/' => {
+                p += 1;
+                f += 1;
+            }
+            Some(c) if c == normalize(file_name[f]) => {
+                p += 1;
+                f += 1;
+            }
+            _ => match backtrack {
+                Some((star, tried)) if normalize(file_name[tried]) != b'\\' => {
+                    backtrack = Some((star, tried + 1));
+                    p = star + 1;
+                    f = tried + 1;
+                }
+                _ => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches_within_path_components() {
+        assert!(glob_matches(
+            "unit\\zerg\\*.grp",
+            "unit\\zerg\\zergling.grp"
+        ));
+        assert!(glob_matches("UNIT/Zerg/*.GRP", "unit\\zerg\\Zergling.grp"));
+        assert!(glob_matches(
+            "unit\\*\\?ergling.grp",
+            "unit\\zerg\\zergling.grp"
+        ));
+        assert!(glob_matches("*", "(listfile)"));
+        assert!(!glob_matches("unit\\*.grp", "unit\\zerg\\zergling.grp"));
+        assert!(!glob_matches(
+            "unit\\zerg\\*.grp",
+            "unit\\zerg\\zergling.grp.bak"
+        ));
+        assert!(!glob_matches(
+            "unit\\zerg?zergling.grp",
+            "unit\\zerg\\zergling.grp"
+        ));
+    }
 }
@@ -0,0 +1,213 @@
+use super::errors::*;
+use super::wpe::WPEs;
+use byteorder::{ByteOrder, LittleEndian};
+use std::io::Cursor;
+
+/// A frame of a GRP as palette indices. Palette index 0 is transparent.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Frame {
+    /// Horizontal position of the frame within the GRP's bounds
+    pub x_offset: usize,
+    /// Vertical position of the frame within the GRP's bounds
+    pub y_offset: usize,
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Frame {
+    /// The frame mirrored left to right within a GRP `grp_width` pixels wide, as units
+    /// facing west are drawn
+    pub fn flipped(&self, grp_width: usize) -> Frame {
+        Frame {
+            x_offset: grp_width.saturating_sub(self.x_offset + self.width),
+            y_offset: self.y_offset,
+            width: self.width,
+            height: self.height,
+            pixels: self
+                .pixels
+                .chunks(self.width.max(1))
+                .flat_map(|row| row.iter().rev().copied())
+                .collect(),
+        }
+    }
+
+    /// Resolves every pixel through `palette` to RGBA, with index 0 fully transparent
+    pub fn to_rgba(&self, palette: &WPEs) -> Vec<[u8; 4]> {
+        self.pixels
+            .iter()
+            .map(|color_index| match *color_index {
+                0 => [0, 0, 0, 0],
+                i => {
+                    let [r, g, b] = palette[i as usize].0;
+                    [r, g, b, 0xFF]
+                }
+            })
+            .collect()
+    }
+}
+
+/// Sprite graphics: every frame of a unit, building or effect in every direction it faces.
+#[derive(Debug, Clone)]
+pub struct GRP {
+    /// Width of the bounds every frame is positioned in
+    pub width: usize,
+    /// Height of the bounds every frame is positioned in
+    pub height: usize,
+    pub frames: Vec<Frame>,
+}
+
+impl GRP {
+    const HEADER_SIZE: usize = 6;
+    const FRAME_HEADER_SIZE: usize = 8;
+    const SKIP: u8 = 0x80;
+    const REPEAT: u8 = 0x40;
+
+    pub fn from_buffer(cursor: &mut Cursor<&Vec<u8>>) -> Result<GRP> {
+        let start = cursor.position() as usize;
+        let buf = cursor
+            .get_ref()
+            .get(start..)
+            .filter(|buf| buf.len() >= GRP::HEADER_SIZE)
+            .ok_or_else(|| format!("failed to read grp header at position: '{}'", start))?;
+
+        let frame_count = LittleEndian::read_u16(&buf[0..]) as usize;
+        let width = LittleEndian::read_u16(&buf[2..]) as usize;
+        let height = LittleEndian::read_u16(&buf[4..]) as usize;
+
+        let mut frames = Vec::with_capacity(frame_count);
+        let mut end = GRP::HEADER_SIZE + frame_count * GRP::FRAME_HEADER_SIZE;
+        for i in 0..frame_count {
+            let header = buf
+                .get(GRP::HEADER_SIZE + i * GRP::FRAME_HEADER_SIZE..)
+                .filter(|header| header.len() >= GRP::FRAME_HEADER_SIZE)
+                .ok_or_else(|| format!("failed to read grp frame {} header", i))?;
+            let (frame, frame_end) = GRP::read_frame(
+                buf,
+                header[0] as usize,
+                header[1] as usize,
+                header[2] as usize,
+                header[3] as usize,
+                LittleEndian::read_u32(&header[4..]) as usize,
+            )
+            .chain_err(|| format!("failed to read grp frame {}", i))?;
+
+            frames.push(frame);
+            end = end.max(frame_end);
+        }
+        cursor.set_position((start + end) as u64);
+
+        Ok(GRP {
+            width,
+            height,
+            frames,
+        })
+    }
+
+    /// Decodes the run length encoded rows of a frame, returning it and where its data ends
+    fn read_frame(
+        buf: &[u8],
+        x_offset: usize,
+        y_offset: usize,
+        width: usize,
+        height: usize,
+        offset: usize,
+    ) -> Result<(Frame, usize)> {
+        let out_of_bounds = |position: usize| format!("data out of bounds at {}", position);
+        let line_offsets = buf
+            .get(offset..offset + height * 2)
+            .ok_or_else(|| out_of_bounds(offset))?;
+
+        let mut pixels = vec![0u8; width * height];
+        let mut end = offset + height * 2;
+        for (y, row) in pixels.chunks_mut(width.max(1)).take(height).enumerate() {
+            let mut position = offset + LittleEndian::read_u16(&line_offsets[y * 2..]) as usize;
+            let mut x = 0;
+            while x < width {
+                let control = *buf.get(position).ok_or_else(|| out_of_bounds(position))?;
+                position += 1;
+
+                if control & GRP::SKIP != 0 {
+                    x += (control & !GRP::SKIP) as usize;
+                } else if control & GRP::REPEAT != 0 {
+                    let color_index = *buf.get(position).ok_or_else(|| out_of_bounds(position))?;
+                    position += 1;
+                    let count = ((control & !GRP::REPEAT) as usize).min(width - x);
+                    row[x..x + count]
+                        .iter_mut()
+                        .for_each(|pixel| *pixel = color_index);
+                    x += count;
+                } else {
+                    let count = (control as usize).min(width - x);
+                    let literal = buf
+                        .get(position..position + count)
+                        .ok_or_else(|| out_of_bounds(position))?;
+                    row[x..x + count].copy_from_slice(literal);
+                    position += count;
+                    x += count;
+                }
+            }
+            end = end.max(position);
+        }
+
+        Ok((
+            Frame {
+                x_offset,
+                y_offset,
+                width,
+                height,
+                pixels,
+            },
+            end,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grp_decodes_run_length_encoded_rows() {
+        #[rustfmt::skip]
+        let buf = vec![
+            // one frame in 8x4 bounds
+            1, 0, 8, 0, 4, 0,
+            // at (2, 1), 4x2, rows at offset 14
+            2, 1, 4, 2, 14, 0, 0, 0,
+            // line offsets
+            4, 0, 9, 0,
+            // skip 1, two literals, skip 1
+            0x81, 2, 5, 6, 0x81,
+            // four of color 9
+            0x44, 9,
+            // not part of the GRP
+            7,
+        ];
+        let mut cursor = Cursor::new(&buf);
+
+        let grp = GRP::from_buffer(&mut cursor).unwrap();
+
+        assert_eq!((grp.width, grp.height, grp.frames.len()), (8, 4, 1));
+        let frame = &grp.frames[0];
+        assert_eq!((frame.x_offset, frame.y_offset), (2, 1));
+        assert_eq!(frame.pixels, vec![0, 5, 6, 0, 9, 9, 9, 9]);
+        assert_eq!(cursor.position(), 25);
+
+        let flipped = frame.flipped(grp.width);
+        assert_eq!(flipped.x_offset, 2);
+        assert_eq!(flipped.pixels, vec![0, 6, 5, 0, 9, 9, 9, 9]);
+
+        let palette = WPEs(
+            (0..=255u8)
+                .map(|i| super::super::wpe::WPE([i, i, i]))
+                .collect(),
+        );
+        assert_eq!(
+            frame.to_rgba(&palette)[..2],
+            [[0, 0, 0, 0], [5, 5, 5, 0xFF]]
+        );
+
+        assert!(GRP::from_buffer(&mut Cursor::new(&buf[..20].to_vec())).is_err());
+    }
+}
@@ -0,0 +1,948 @@
+use super::errors::*;
+use byteorder::{ByteOrder, LittleEndian};
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::io::Cursor;
+
+/// The animations an iscript can have, in the order of its header.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, FromPrimitive)]
+pub enum Animation {
+    Init = 0,
+    Death,
+    GroundAttackInit,
+    AirAttackInit,
+    Unused1,
+    GroundAttackRepeat,
+    AirAttackRepeat,
+    CastSpell,
+    GroundAttackToIdle,
+    AirAttackToIdle,
+    Unused2,
+    Walking,
+    WalkingToIdle,
+    SpecialState1,
+    SpecialState2,
+    AlmostBuilt,
+    Built,
+    Landing,
+    LiftOff,
+    IsWorking,
+    WorkingToIdle,
+    WarpIn,
+    Unused3,
+    StarEditInit,
+    Disable,
+    Burrow,
+    UnBurrow,
+    Enable,
+}
+
+impl Animation {
+    /// The name the game's tools use for the animation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Animation::Init => "Init",
+            Animation::Death => "Death",
+            Animation::GroundAttackInit => "GndAttkInit",
+            Animation::AirAttackInit => "AirAttkInit",
+            Animation::Unused1 => "Unused1",
+            Animation::GroundAttackRepeat => "GndAttkRpt",
+            Animation::AirAttackRepeat => "AirAttkRpt",
+            Animation::CastSpell => "CastSpell",
+            Animation::GroundAttackToIdle => "GndAttkToIdle",
+            Animation::AirAttackToIdle => "AirAttkToIdle",
+            Animation::Unused2 => "Unused2",
+            Animation::Walking => "Walking",
+            Animation::WalkingToIdle => "WalkingToIdle",
+            Animation::SpecialState1 => "SpecialState1",
+            Animation::SpecialState2 => "SpecialState2",
+            Animation::AlmostBuilt => "AlmostBuilt",
+            Animation::Built => "Built",
+            Animation::Landing => "Landing",
+            Animation::LiftOff => "LiftOff",
+            Animation::IsWorking => "IsWorking",
+            Animation::WorkingToIdle => "WorkingToIdle",
+            Animation::WarpIn => "WarpIn",
+            Animation::Unused3 => "Unused3",
+            Animation::StarEditInit => "StarEditInit",
+            Animation::Disable => "Disable",
+            Animation::Burrow => "Burrow",
+            Animation::UnBurrow => "UnBurrow",
+            Animation::Enable => "Enable",
+        }
+    }
+}
+
+/// The kind of an opcode's argument.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ArgKind {
+    U8,
+    S8,
+    U16,
+    /// Offset of an instruction to jump to
+    Label,
+    /// A count followed by that many sound ids
+    Sounds,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Arg {
+    U8(u8),
+    S8(i8),
+    U16(u16),
+    Label(u16),
+    Sounds(Vec<u16>),
+}
+
+impl Arg {
+    fn as_u8(&self) -> u8 {
+        match self {
+            Arg::U8(value) => *value,
+            Arg::S8(value) => *value as u8,
+            _ => 0,
+        }
+    }
+
+    fn as_i8(&self) -> i8 {
+        self.as_u8() as i8
+    }
+
+    fn as_u16(&self) -> u16 {
+        match self {
+            Arg::U16(value) | Arg::Label(value) => *value,
+            _ => self.as_u8() as u16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, FromPrimitive)]
+pub enum Opcode {
+    PlayFram = 0x00,
+    PlayFramTile,
+    SetHorPos,
+    SetVertPos,
+    SetPos,
+    Wait,
+    WaitRand,
+    Goto,
+    ImgOl,
+    ImgUl,
+    ImgOlOrig,
+    SwitchUl,
+    Unknown0C,
+    ImgOlUseLo,
+    ImgUlUseLo,
+    SprOl,
+    HighSprOl,
+    LowSprUl,
+    UflUnstable,
+    SprUlUseLo,
+    SprUl,
+    SprOlUseLo,
+    End,
+    SetFlipState,
+    PlaySnd,
+    PlaySndRand,
+    PlaySndBtwn,
+    DoMissileDmg,
+    AttackMelee,
+    FollowMainGraphic,
+    RandCondJmp,
+    TurnCcwise,
+    TurnCwise,
+    Turn1Cwise,
+    TurnRand,
+    SetSpawnFrame,
+    SigOrder,
+    AttackWith,
+    Attack,
+    CastSpell,
+    UseWeapon,
+    Move,
+    GotoRepeatAttk,
+    EngFrame,
+    EngSet,
+    Unknown2D,
+    NoBrkCodeStart,
+    NoBrkCodeEnd,
+    IgnoreRest,
+    AttkShiftProj,
+    TmpRmGraphicStart,
+    TmpRmGraphicEnd,
+    SetFlDirect,
+    Call,
+    Return,
+    SetFlSpeed,
+    CreateGasOverlays,
+    PwrUpCondJmp,
+    TrgtRangeCondJmp,
+    TrgtArcCondJmp,
+    CurDirectCondJmp,
+    ImgUlNextId,
+    Unknown3E,
+    LiftOffCondJmp,
+    WarpOverlay,
+    OrderDone,
+    GrdSprOl,
+    Unknown43,
+    DoGrdDamage,
+}
+
+impl Opcode {
+    /// Names and arguments of every opcode, in opcode order
+    const OPCODES: [(&'static str, &'static [ArgKind]); 0x45] = [
+        ("playfram", &[ArgKind::U16]),
+        ("playframtile", &[ArgKind::U16]),
+        ("sethorpos", &[ArgKind::S8]),
+        ("setvertpos", &[ArgKind::S8]),
+        ("setpos", &[ArgKind::S8, ArgKind::S8]),
+        ("wait", &[ArgKind::U8]),
+        ("waitrand", &[ArgKind::U8, ArgKind::U8]),
+        ("goto", &[ArgKind::Label]),
+        ("imgol", &[ArgKind::U16, ArgKind::S8, ArgKind::S8]),
+        ("imgul", &[ArgKind::U16, ArgKind::S8, ArgKind::S8]),
+        ("imgolorig", &[ArgKind::U16]),
+        ("switchul", &[ArgKind::U16]),
+        ("__0c", &[]),
+        ("imgoluselo", &[ArgKind::U16, ArgKind::S8, ArgKind::S8]),
+        ("imguluselo", &[ArgKind::U16, ArgKind::S8, ArgKind::S8]),
+        ("sprol", &[ArgKind::U16, ArgKind::S8, ArgKind::S8]),
+        ("highsprol", &[ArgKind::U16, ArgKind::S8, ArgKind::S8]),
+        ("lowsprul", &[ArgKind::U16, ArgKind::S8, ArgKind::S8]),
+        ("uflunstable", &[ArgKind::U16]),
+        ("spruluselo", &[ArgKind::U16, ArgKind::S8, ArgKind::S8]),
+        ("sprul", &[ArgKind::U16, ArgKind::S8, ArgKind::S8]),
+        ("sproluselo", &[ArgKind::U16, ArgKind::U8]),
+        ("end", &[]),
+        ("setflipstate", &[ArgKind::U8]),
+        ("playsnd", &[ArgKind::U16]),
+        ("playsndrand", &[ArgKind::Sounds]),
+        ("playsndbtwn", &[ArgKind::U16, ArgKind::U16]),
+        ("domissiledmg", &[]),
+        ("attackmelee", &[ArgKind::Sounds]),
+        ("followmaingraphic", &[]),
+        ("randcondjmp", &[ArgKind::U8, ArgKind::Label]),
+        ("turnccwise", &[ArgKind::U8]),
+        ("turncwise", &[ArgKind::U8]),
+        ("turn1cwise", &[]),
+        ("turnrand", &[ArgKind::U8]),
+        ("setspawnframe", &[ArgKind::U8]),
+        ("sigorder", &[ArgKind::U8]),
+        ("attackwith", &[ArgKind::U8]),
+        ("attack", &[]),
+        ("castspell", &[]),
+        ("useweapon", &[ArgKind::U8]),
+        ("move", &[ArgKind::U8]),
+        ("gotorepeatattk", &[]),
+        ("engframe", &[ArgKind::U8]),
+        ("engset", &[ArgKind::U8]),
+        ("__2d", &[]),
+        ("nobrkcodestart", &[]),
+        ("nobrkcodeend", &[]),
+        ("ignorerest", &[]),
+        ("attkshiftproj", &[ArgKind::U8]),
+        ("tmprmgraphicstart", &[]),
+        ("tmprmgraphicend", &[]),
+        ("setfldirect", &[ArgKind::U8]),
+        ("call", &[ArgKind::Label]),
+        ("return", &[]),
+        ("setflspeed", &[ArgKind::U16]),
+        ("creategasoverlays", &[ArgKind::U8]),
+        ("pwrupcondjmp", &[ArgKind::Label]),
+        ("trgtrangecondjmp", &[ArgKind::U16, ArgKind::Label]),
+        (
+            "trgtarccondjmp",
+            &[ArgKind::U16, ArgKind::U16, ArgKind::Label],
+        ),
+        (
+            "curdirectcondjmp",
+            &[ArgKind::U16, ArgKind::U16, ArgKind::Label],
+        ),
+        ("imgulnextid", &[ArgKind::S8, ArgKind::S8]),
+        ("__3e", &[]),
+        ("liftoffcondjmp", &[ArgKind::Label]),
+        ("warpoverlay", &[ArgKind::U16]),
+        ("orderdone", &[ArgKind::U8]),
+        ("grdsprol", &[ArgKind::U16, ArgKind::S8, ArgKind::S8]),
+        ("__43", &[]),
+        ("dogrddamage", &[]),
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        Opcode::OPCODES[*self as usize].0
+    }
+
+    pub fn args(&self) -> &'static [ArgKind] {
+        Opcode::OPCODES[*self as usize].1
+    }
+
+    /// Whether execution never continues to the next instruction
+    fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            Opcode::End | Opcode::Goto | Opcode::Return | Opcode::IgnoreRest
+        )
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    pub args: Vec<Arg>,
+    /// Offset of the next instruction
+    pub next: u16,
+}
+
+impl Instruction {
+    /// Offsets that the instruction may jump to
+    pub fn labels(&self) -> impl Iterator<Item = u16> + '_ {
+        self.args.iter().filter_map(|arg| match arg {
+            Arg::Label(label) => Some(*label),
+            _ => None,
+        })
+    }
+}
+
+/// The animations of one iscript id.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Script {
+    pub id: u16,
+    /// Which set of animations the header has
+    pub kind: u8,
+    /// Offset of each animation in header order, or None if it has none
+    pub animations: Vec<Option<u16>>,
+}
+
+impl Script {
+    pub fn animation(&self, animation: Animation) -> Option<u16> {
+        self.animations.get(animation as usize).copied().flatten()
+    }
+}
+
+/// `scripts\iscript.bin`: the animation scripts of every image.
+#[derive(Debug, Clone, Default)]
+pub struct Iscript {
+    pub scripts: BTreeMap<u16, Script>,
+    /// Every instruction reachable from an animation, by offset
+    pub instructions: BTreeMap<u16, Instruction>,
+}
+
+impl Iscript {
+    pub const FILE_NAME: &'static str = "scripts\\iscript.bin";
+    const MAGIC: &'static [u8; 4] = b"SCPE";
+    const END_OF_TABLE: u16 = 0xFFFF;
+
+    /// Animations in the header of each script kind
+    fn animation_count(kind: u8) -> Option<usize> {
+        match kind {
+            0 | 1 => Some(2),
+            2 => Some(4),
+            12 | 13 => Some(14),
+            14 | 15 => Some(16),
+            20 | 21 => Some(22),
+            23 => Some(24),
+            24 => Some(26),
+            26..=29 => Some(28),
+            _ => None,
+        }
+    }
+
+    pub fn from_buffer(cursor: &mut Cursor<&Vec<u8>>) -> Result<Iscript> {
+        let start = cursor.position() as usize;
+        let buf = cursor
+            .get_ref()
+            .get(start..)
+            .filter(|buf| buf.len() >= 2)
+            .ok_or_else(|| format!("failed to read iscript at position: '{}'", start))?;
+        let read_u16 = |position: usize| {
+            buf.get(position..position + 2)
+                .map(LittleEndian::read_u16)
+                .ok_or_else(|| format!("iscript data out of bounds at {}", position))
+        };
+
+        let mut scripts = BTreeMap::new();
+        let mut entry = read_u16(0)? as usize;
+        loop {
+            let id = read_u16(entry)?;
+            if id == Iscript::END_OF_TABLE {
+                break;
+            }
+            let offset = read_u16(entry + 2)? as usize;
+            entry += 4;
+
+            if buf.get(offset..offset + 4) != Some(&Iscript::MAGIC[..]) {
+                return Err(format!("iscript {} has no header at {}", id, offset).into());
+            }
+            let kind = *buf
+                .get(offset + 4)
+                .ok_or_else(|| format!("iscript data out of bounds at {}", offset + 4))?;
+            let count = Iscript::animation_count(kind)
+                .ok_or_else(|| format!("iscript {} has an unknown header type {}", id, kind))?;
+            let animations = (0..count)
+                .map(|i| {
+                    read_u16(offset + 8 + i * 2).map(|offset| Some(offset).filter(|o| *o != 0))
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+
+            scripts.insert(
+                id,
+                Script {
+                    id,
+                    kind,
+                    animations,
+                },
+            );
+        }
+
+        let mut instructions = BTreeMap::new();
+        let mut pending = scripts
+            .values()
+            .flat_map(|script| script.animations.iter().flatten().copied())
+            .collect::<Vec<_>>();
+        while let Some(mut offset) = pending.pop() {
+            while !instructions.contains_key(&offset) {
+                let instruction = Iscript::read_instruction(buf, offset)?;
+                pending.extend(instruction.labels());
+                let terminal = instruction.opcode.is_terminal();
+                let next = instruction.next;
+                instructions.insert(offset, instruction);
+                if terminal {
+                    break;
+                }
+                offset = next;
+            }
+        }
+        cursor.set_position(cursor.get_ref().len() as u64);
+
+        Ok(Iscript {
+            scripts,
+            instructions,
+        })
+    }
+
+    fn read_instruction(buf: &[u8], offset: u16) -> Result<Instruction> {
+        let out_of_bounds = |position: usize| format!("iscript data out of bounds at {}", position);
+        let mut position = offset as usize;
+        let byte = *buf.get(position).ok_or_else(|| out_of_bounds(position))?;
+        let opcode = Opcode::from_u8(byte)
+            .ok_or_else(|| format!("unknown iscript opcode {:#04x} at {}", byte, offset))?;
+        position += 1;
+
+        let mut args = Vec::with_capacity(opcode.args().len());
+        for kind in opcode.args() {
+            let mut read_u8 = || -> Result<u8> {
+                let value = *buf.get(position).ok_or_else(|| out_of_bounds(position))?;
+                position += 1;
+                Ok(value)
+            };
+            let arg = match kind {
+                ArgKind::U8 => Arg::U8(read_u8()?),
+                ArgKind::S8 => Arg::S8(read_u8()? as i8),
+                ArgKind::U16 | ArgKind::Label => {
+                    let value = u16::from_le_bytes([read_u8()?, read_u8()?]);
+                    if *kind == ArgKind::Label {
+                        Arg::Label(value)
+                    } else {
+                        Arg::U16(value)
+                    }
+                }
+                ArgKind::Sounds => {
+                    let count = read_u8()?;
+                    let sounds = (0..count)
+                        .map(|_| Ok(u16::from_le_bytes([read_u8()?, read_u8()?])))
+                        .collect::<Result<Vec<_>>>()?;
+                    Arg::Sounds(sounds)
+                }
+            };
+            args.push(arg);
+        }
+        if position > u16::MAX as usize {
+            return Err(out_of_bounds(position).into());
+        }
+
+        Ok(Instruction {
+            opcode,
+            args,
+            next: position as u16,
+        })
+    }
+
+    /// The headers and code as text, with a label before every jump target
+    pub fn disassemble(&self) -> String {
+        let mut labels = self
+            .instructions
+            .values()
+            .flat_map(|instruction| instruction.labels())
+            .collect::<BTreeSet<_>>();
+        labels.extend(
+            self.scripts
+                .values()
+                .flat_map(|script| script.animations.iter().flatten().copied()),
+        );
+
+        let mut text = String::new();
+        for script in self.scripts.values() {
+            writeln!(text, ".headerstart").unwrap();
+            writeln!(text, "IsId {}", script.id).unwrap();
+            writeln!(text, "Type {}", script.kind).unwrap();
+            for (i, offset) in script.animations.iter().enumerate() {
+                let name = Animation::from_usize(i).map_or("Unknown", |a| a.as_str());
+                match offset {
+                    Some(offset) => writeln!(text, "{} L{:04X}", name, offset).unwrap(),
+                    None => writeln!(text, "{} [NONE]", name).unwrap(),
+                }
+            }
+            writeln!(text, ".headerend").unwrap();
+        }
+
+        let mut previous_end = None;
+        for (offset, instruction) in &self.instructions {
+            if previous_end.is_some() && previous_end != Some(*offset) {
+                writeln!(text).unwrap();
+            }
+            if labels.contains(offset) {
+                writeln!(text, "L{:04X}:", offset).unwrap();
+            }
+
+            write!(text, "\t{}", instruction.opcode.as_str()).unwrap();
+            for arg in &instruction.args {
+                match arg {
+                    Arg::U8(value) => write!(text, " {}", value),
+                    Arg::S8(value) => write!(text, " {}", value),
+                    Arg::U16(value) => write!(text, " {}", value),
+                    Arg::Label(label) => write!(text, " L{:04X}", label),
+                    Arg::Sounds(sounds) => sounds
+                        .iter()
+                        .try_for_each(|sound| write!(text, " {}", sound)),
+                }
+                .unwrap();
+            }
+            writeln!(text).unwrap();
+            previous_end = Some(instruction.next);
+        }
+
+        text
+    }
+}
+
+/// What running an iscript asks of the game.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Event {
+    /// An image created over (`above`) or under the running one, offset in pixels
+    Image {
+        image: u16,
+        x: i8,
+        y: i8,
+        above: bool,
+    },
+    /// A sprite created over (`above`) or under the running image, offset in pixels
+    Sprite {
+        sprite: u16,
+        x: i8,
+        y: i8,
+        above: bool,
+    },
+    Sound(u16),
+    /// Fire the weapon for the target, or weapon 1 (ground) or 2 (air)
+    Attack(Option<u8>),
+    /// Fire a specific weapon from `weapons.dat`
+    UseWeapon(u8),
+    CastSpell,
+    /// Damage the target directly, as melee attacks and landed projectiles do
+    Damage,
+    /// The attack may repeat from here if the unit is ordered to attack again
+    RepeatAttack,
+    /// Move the unit forward this many pixels
+    Move(u8),
+    SetSpeed(u16),
+    /// Signal the unit's order
+    Signal(u8),
+    OrderDone(u8),
+    /// The image is removed
+    End,
+    /// Any other instruction that affects something outside the image
+    Other(Instruction),
+}
+
+/// State that conditional jumps depend on, kept up to date by the caller.
+#[derive(Debug, Clone, Default)]
+pub struct Conditions {
+    pub powered: bool,
+    pub target_in_range: bool,
+    pub target_in_arc: bool,
+    pub lifted_off: bool,
+}
+
+/// Steps an image through its iscript one game frame at a time.
+#[derive(Debug, Clone)]
+pub struct IscriptRunner<'a> {
+    iscript: &'a Iscript,
+    script: &'a Script,
+    position: Option<u16>,
+    wait: u8,
+    return_position: Option<u16>,
+    /// Frame set by the last `playfram`
+    pub frame: u16,
+    /// One of 32 directions, clockwise from north
+    pub direction: u8,
+    pub flipped: bool,
+    pub x: i8,
+    pub y: i8,
+    /// Whether the image is hidden by `tmprmgraphicstart`
+    pub hidden: bool,
+    /// Whether the running code may not be interrupted by a new order
+    pub uninterruptible: bool,
+    pub conditions: Conditions,
+    /// State of the game's random number generator
+    pub seed: u32,
+}
+
+impl<'a> IscriptRunner<'a> {
+    /// Instructions a single frame may run before the script is considered stuck
+    const MAX_STEPS: usize = 10_000;
+    const DIRECTIONS: u8 = 32;
+
+    pub fn new(iscript: &'a Iscript, id: u16) -> Result<IscriptRunner<'a>> {
+        let script = iscript
+            .scripts
+            .get(&id)
+            .ok_or_else(|| format!("iscript {} does not exist", id))?;
+
+        Ok(IscriptRunner {
+            iscript,
+            script,
+            position: None,
+            wait: 0,
+            return_position: None,
+            frame: 0,
+            direction: 0,
+            flipped: false,
+            x: 0,
+            y: 0,
+            hidden: false,
+            uninterruptible: false,
+            conditions: Conditions::default(),
+            seed: 1,
+        })
+    }
+
+    /// Starts `animation`, returning false if the script does not have it
+    pub fn play(&mut self, animation: Animation) -> bool {
+        match self.script.animation(animation) {
+            Some(offset) => {
+                self.position = Some(offset);
+                self.wait = 0;
+                self.return_position = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether the script reached `end`, or has not started
+    pub fn has_ended(&self) -> bool {
+        self.position.is_none()
+    }
+
+    /// The GRP frame to draw and whether it is mirrored. Images with graphic turns
+    /// have 17 frames per animation step, for the directions from north to south.
+    pub fn grp_frame(&self, graphic_turns: bool) -> (usize, bool) {
+        if !graphic_turns {
+            return (self.frame as usize, self.flipped);
+        }
+
+        let direction = self.direction % IscriptRunner::DIRECTIONS;
+        if direction > IscriptRunner::DIRECTIONS / 2 {
+            (
+                self.frame as usize + (IscriptRunner::DIRECTIONS - direction) as usize,
+                true,
+            )
+        } else {
+            (self.frame as usize + direction as usize, false)
+        }
+    }
+
+    fn random(&mut self) -> u16 {
+        self.seed = self.seed.wrapping_mul(22_695_477).wrapping_add(1);
+        ((self.seed >> 16) & 0x7FFF) as u16
+    }
+
+    fn turn(&mut self, by: i16) {
+        let directions = IscriptRunner::DIRECTIONS as i16;
+        self.direction = (self.direction as i16 + by).rem_euclid(directions) as u8;
+    }
+
+    /// Runs one game frame, returning what the script asked for
+    pub fn step(&mut self) -> Result<Vec<Event>> {
+        let mut events = Vec::new();
+        if self.wait > 0 {
+            self.wait -= 1;
+            return Ok(events);
+        }
+
+        for _ in 0..IscriptRunner::MAX_STEPS {
+            let offset = match self.position {
+                Some(offset) => offset,
+                None => return Ok(events),
+            };
+            let instruction = self
+                .iscript
+                .instructions
+                .get(&offset)
+                .ok_or_else(|| format!("no iscript instruction at {}", offset))?;
+            let args = &instruction.args;
+            let mut next = Some(instruction.next);
+
+            match instruction.opcode {
+                Opcode::PlayFram | Opcode::PlayFramTile => self.frame = args[0].as_u16(),
+                Opcode::SetHorPos => self.x = args[0].as_i8(),
+                Opcode::SetVertPos => self.y = args[0].as_i8(),
+                Opcode::SetPos => {
+                    self.x = args[0].as_i8();
+                    self.y = args[1].as_i8();
+                }
+                Opcode::Wait => {
+                    self.wait = args[0].as_u8().saturating_sub(1);
+                    self.position = next;
+                    return Ok(events);
+                }
+                Opcode::WaitRand => {
+                    let (min, max) = (args[0].as_u8(), args[1].as_u8());
+                    let span = max.saturating_sub(min) as u16 + 1;
+                    let wait = min as u16 + self.random() % span;
+                    self.wait = (wait as u8).saturating_sub(1);
+                    self.position = next;
+                    return Ok(events);
+                }
+                Opcode::Goto => next = Some(args[0].as_u16()),
+                Opcode::ImgOl | Opcode::ImgOlUseLo => events.push(Event::Image {
+                    image: args[0].as_u16(),
+                    x: args[1].as_i8(),
+                    y: args[2].as_i8(),
+                    above: true,
+                }),
+                Opcode::ImgUl | Opcode::ImgUlUseLo => events.push(Event::Image {
+                    image: args[0].as_u16(),
+                    x: args[1].as_i8(),
+                    y: args[2].as_i8(),
+                    above: false,
+                }),
+                Opcode::ImgOlOrig => events.push(Event::Image {
+                    image: args[0].as_u16(),
+                    x: 0,
+                    y: 0,
+                    above: true,
+                }),
+                Opcode::SwitchUl => events.push(Event::Image {
+                    image: args[0].as_u16(),
+                    x: 0,
+                    y: 0,
+                    above: false,
+                }),
+                Opcode::SprOl | Opcode::HighSprOl | Opcode::GrdSprOl => {
+                    events.push(Event::Sprite {
+                        sprite: args[0].as_u16(),
+                        x: args[1].as_i8(),
+                        y: args[2].as_i8(),
+                        above: true,
+                    })
+                }
+                Opcode::LowSprUl | Opcode::SprUl | Opcode::SprUlUseLo => {
+                    events.push(Event::Sprite {
+                        sprite: args[0].as_u16(),
+                        x: args[1].as_i8(),
+                        y: args[2].as_i8(),
+                        above: false,
+                    })
+                }
+                Opcode::SprOlUseLo => events.push(Event::Sprite {
+                    sprite: args[0].as_u16(),
+                    x: 0,
+                    y: 0,
+                    above: true,
+                }),
+                Opcode::End => {
+                    self.position = None;
+                    events.push(Event::End);
+                    return Ok(events);
+                }
+                Opcode::SetFlipState => self.flipped = args[0].as_u8() != 0,
+                Opcode::PlaySnd => events.push(Event::Sound(args[0].as_u16())),
+                Opcode::PlaySndRand => {
+                    if let Arg::Sounds(sounds) = &args[0] {
+                        if !sounds.is_empty() {
+                            let i = self.random() as usize % sounds.len();
+                            events.push(Event::Sound(sounds[i]));
+                        }
+                    }
+                }
+                Opcode::PlaySndBtwn => {
+                    let (min, max) = (args[0].as_u16(), args[1].as_u16());
+                    let span = max.saturating_sub(min) as u32 + 1;
+                    let sound = min as u32 + self.random() as u32 % span;
+                    events.push(Event::Sound(sound as u16));
+                }
+                Opcode::DoMissileDmg | Opcode::DoGrdDamage => events.push(Event::Damage),
+                Opcode::AttackMelee => {
+                    events.push(Event::Damage);
+                    if let Arg::Sounds(sounds) = &args[0] {
+                        if !sounds.is_empty() {
+                            let i = self.random() as usize % sounds.len();
+                            events.push(Event::Sound(sounds[i]));
+                        }
+                    }
+                }
+                Opcode::RandCondJmp => {
+                    if (self.random() & 0xFF) as u8 <= args[0].as_u8() {
+                        next = Some(args[1].as_u16());
+                    }
+                }
+                Opcode::TurnCcwise => self.turn(-(args[0].as_u8() as i16)),
+                Opcode::TurnCwise => self.turn(args[0].as_u8() as i16),
+                Opcode::Turn1Cwise => self.turn(1),
+                Opcode::TurnRand => {
+                    let by = args[0].as_u8() as i16;
+                    if self.random() % 4 == 1 {
+                        self.turn(-by);
+                    } else {
+                        self.turn(by);
+                    }
+                }
+                Opcode::SigOrder => events.push(Event::Signal(args[0].as_u8())),
+                Opcode::AttackWith => events.push(Event::Attack(Some(args[0].as_u8()))),
+                Opcode::Attack => events.push(Event::Attack(None)),
+                Opcode::CastSpell => events.push(Event::CastSpell),
+                Opcode::UseWeapon => events.push(Event::UseWeapon(args[0].as_u8())),
+                Opcode::Move => events.push(Event::Move(args[0].as_u8())),
+                Opcode::GotoRepeatAttk => events.push(Event::RepeatAttack),
+                Opcode::NoBrkCodeStart => self.uninterruptible = true,
+                Opcode::NoBrkCodeEnd => self.uninterruptible = false,
+                Opcode::IgnoreRest => return Ok(events),
+                Opcode::TmpRmGraphicStart => self.hidden = true,
+                Opcode::TmpRmGraphicEnd => self.hidden = false,
+                Opcode::SetFlDirect => self.direction = args[0].as_u8() % IscriptRunner::DIRECTIONS,
+                Opcode::Call => {
+                    self.return_position = next;
+                    next = Some(args[0].as_u16());
+                }
+                Opcode::Return => {
+                    next = self.return_position.take();
+                    if next.is_none() {
+                        return Err(format!("iscript returns at {} without a call", offset).into());
+                    }
+                }
+                Opcode::SetFlSpeed => events.push(Event::SetSpeed(args[0].as_u16())),
+                Opcode::PwrUpCondJmp => {
+                    if self.conditions.powered {
+                        next = Some(args[0].as_u16());
+                    }
+                }
+                Opcode::TrgtRangeCondJmp => {
+                    if self.conditions.target_in_range {
+                        next = Some(args[1].as_u16());
+                    }
+                }
+                Opcode::TrgtArcCondJmp => {
+                    if self.conditions.target_in_arc {
+                        next = Some(args[2].as_u16());
+                    }
+                }
+                Opcode::CurDirectCondJmp => {
+                    // angles are in 256ths of a turn
+                    let heading = self.direction as i32 * 8;
+                    let difference = (heading - args[0].as_u16() as i32) as u8 as i8;
+                    if (difference as i32).abs() <= args[1].as_u16() as i32 {
+                        next = Some(args[2].as_u16());
+                    }
+                }
+                Opcode::LiftOffCondJmp => {
+                    if self.conditions.lifted_off {
+                        next = Some(args[0].as_u16());
+                    }
+                }
+                Opcode::OrderDone => events.push(Event::OrderDone(args[0].as_u8())),
+                Opcode::Unknown0C
+                | Opcode::FollowMainGraphic
+                | Opcode::Unknown2D
+                | Opcode::Unknown3E
+                | Opcode::Unknown43 => {}
+                Opcode::UflUnstable
+                | Opcode::SetSpawnFrame
+                | Opcode::EngFrame
+                | Opcode::EngSet
+                | Opcode::AttkShiftProj
+                | Opcode::CreateGasOverlays
+                | Opcode::ImgUlNextId
+                | Opcode::WarpOverlay => events.push(Event::Other(instruction.clone())),
+            }
+
+            self.position = next;
+        }
+
+        Err(format!(
+            "iscript {} ran {} instructions without waiting",
+            self.script.id,
+            IscriptRunner::MAX_STEPS
+        )
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_iscript_disassembles_and_runs_animations() {
+        #[rustfmt::skip]
+        let buf = vec![
+            // offset of the header table
+            40, 0,
+            // script header of type 0 with Init at 16 and Death at 34
+            b'S', b'C', b'P', b'E', 0, 0, 0, 0, 16, 0, 34, 0,
+            // unreachable padding
+            0xFF, 0xFF,
+            // 16: playfram 0, wait 2, playfram 17, attackwith 1, playsnd 7, wait 1, goto 16
+            0x00, 0, 0, 0x05, 2, 0x00, 17, 0, 0x25, 1, 0x18, 7, 0, 0x05, 1, 0x07, 16, 0,
+            // 34: playfram 34, wait 1, end
+            0x00, 34, 0, 0x05, 1, 0x16,
+            // header table with script 5
+            5, 0, 2, 0, 0xFF, 0xFF, 0, 0,
+        ];
+        let iscript = Iscript::from_buffer(&mut Cursor::new(&buf)).unwrap();
+        assert_eq!(iscript.scripts[&5].animation(Animation::Death), Some(34));
+        assert_eq!(iscript.instructions.len(), 10);
+
+        let text = iscript.disassemble();
+        assert!(text.contains("Init L0010\nDeath L0022\n"));
+        assert!(text.contains("L0010:\n\tplayfram 0\n\twait 2\n"));
+        assert!(text.contains("\tgoto L0010\nL0022:\n"));
+
+        let mut runner = IscriptRunner::new(&iscript, 5).unwrap();
+        assert!(runner.play(Animation::Init));
+        assert!(!runner.play(Animation::Walking));
+        assert_eq!(runner.step().unwrap(), vec![]);
+        assert_eq!(runner.step().unwrap(), vec![]);
+        assert_eq!(runner.grp_frame(true), (0, false));
+        assert_eq!(
+            runner.step().unwrap(),
+            vec![Event::Attack(Some(1)), Event::Sound(7)]
+        );
+        runner.direction = 20;
+        assert_eq!(runner.grp_frame(true), (17 + 12, true));
+        runner.step().unwrap();
+        assert_eq!(runner.frame, 0);
+
+        assert!(runner.play(Animation::Death));
+        runner.step().unwrap();
+        assert_eq!(runner.frame, 34);
+        assert_eq!(runner.step().unwrap(), vec![Event::End]);
+        assert!(runner.has_ended());
+
+        let mut corrupt = buf.clone();
+        corrupt[16] = 0x60;
+        assert!(Iscript::from_buffer(&mut Cursor::new(&corrupt)).is_err());
+    }
+}
@@ -4,7 +4,26 @@ extern crate error_chain;
 pub mod errors {
     error_chain! {
         errors {
-            IO
+            NotFound(file_name: String, archive: String) {
+                description("file not found")
+                display("file not found: '{}' in {}", file_name, archive)
+            }
+            Corrupt(file_name: String, archive: String, offset: Option<u64>) {
+                description("corrupt file")
+                display("corrupt file: '{}' in {}{}", file_name, archive, at_offset(*offset))
+            }
+            Io(file_name: String, archive: String, offset: Option<u64>) {
+                description("failed to read file")
+                display("failed to read file: '{}' in {}{}", file_name, archive, at_offset(*offset))
+            }
+            Encrypted(file_name: String, archive: String, offset: Option<u64>) {
+                description("encrypted file")
+                display(
+                    "encrypted file: '{}' in {}{} cannot be decrypted",
+                    file_name,
+                    archive,
+                    at_offset(*offset))
+            }
             AssetNotFound(scenario_type: String, ext: String) {
                 description("asset not found")
                 display("asset not found: '{}.{}'", scenario_type, ext)
@@ -18,23 +37,52 @@ pub mod errors {
             }
         }
     }
+
+    fn at_offset(offset: Option<u64>) -> String {
+        offset
+            .map(|offset| format!(" at offset {}", offset))
+            .unwrap_or_default()
+    }
+
+    impl Error {
+        /// Whether the error only means that the file does not exist, as opposed to
+        /// existing but being unreadable
+        pub fn is_not_found(&self) -> bool {
+            matches!(self.kind(), ErrorKind::NotFound(..))
+        }
+    }
 }
 use crossbeam;
 pub use errors::*;
 use std::sync::Arc;
 
+pub mod cache;
 pub mod chk;
 pub mod cv5;
+pub mod dat;
+pub mod directory;
+#[cfg(any(test, feature = "fixtures"))]
+pub mod fixtures;
 pub mod fs;
+pub mod grp;
+pub mod iscript;
+pub mod listfile;
 pub mod loader;
 pub mod map;
+pub mod memory;
 pub mod mpq;
+pub mod overlay;
+pub mod pcx;
+pub mod placement;
+pub mod tbl;
 pub mod vf4;
 pub mod vr4;
 pub mod vx4;
+pub mod wav;
 pub mod wpe;
 
 pub trait AssetLoader {
+    fn tileset(&self) -> loader::Tileset;
     fn load_cv5s(&self) -> Result<cv5::CV5s>;
     fn load_vf4s(&self) -> Result<vf4::VF4s>;
     fn load_vx4s(&self) -> Result<vx4::VX4s>;
@@ -43,6 +91,7 @@ pub trait AssetLoader {
 }
 
 pub struct Assets {
+    pub tileset: loader::Tileset,
     pub cv5s: cv5::CV5s,
     pub vf4s: vf4::VF4s,
     pub vx4s: vx4::VX4s,
@@ -71,6 +120,7 @@ impl Assets {
         .map_err(|_| "failed to load assets concurrently")?;
 
         Ok(Assets {
+            tileset: asset_loader.tileset(),
             cv5s: cv5s?,
             vf4s: vf4s?,
             vx4s: vx4s?,
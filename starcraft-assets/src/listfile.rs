@@ -0,0 +1,75 @@
+use std::collections::BTreeMap;
+
+/// The file names listed in a `(listfile)`.
+///
+/// MPQ archives only store hashes of their file names, so listfiles, either inside the
+/// archive or shipped separately, are the only way to enumerate them.
+#[derive(Debug, Clone, Default)]
+pub struct Listfile {
+    /// file names keyed by their lowercase form
+    names: BTreeMap<String, String>,
+}
+
+impl Listfile {
+    pub fn new() -> Listfile {
+        Listfile::default()
+    }
+
+    /// Parses a listfile, whose names are separated by line breaks or semicolons
+    pub fn from_buffer(buf: &[u8]) -> Listfile {
+        let mut listfile = Listfile::new();
+        listfile.extend_from_buffer(buf);
+
+        listfile
+    }
+
+    pub fn extend_from_buffer(&mut self, buf: &[u8]) {
+        String::from_utf8_lossy(buf)
+            .split(&['\r', '\n', ';'][..])
+            .map(|name| name.trim())
+            .filter(|name| !name.is_empty())
+            .for_each(|name| self.insert(name));
+    }
+
+    pub fn insert(&mut self, file_name: &str) {
+        self.names
+            .entry(file_name.to_ascii_lowercase().replace('/', "\\"))
+            .or_insert_with(|| file_name.into());
+    }
+
+    pub fn contains(&self, file_name: &str) -> bool {
+        self.names
+            .contains_key(&file_name.to_ascii_lowercase().replace('/', "\\"))
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// File names in case-insensitive order
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.names.values().map(|name| name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listfile_deduplicates_names_case_insensitively() {
+        let listfile = Listfile::from_buffer(
+            b"unit\\zerg\\zergling.grp\r\nUNIT\\ZERG\\ZERGLING.GRP;arr\\units.dat\n\n",
+        );
+
+        assert_eq!(
+            listfile.iter().collect::<Vec<_>>(),
+            vec!["arr\\units.dat", "unit\\zerg\\zergling.grp"]
+        );
+        assert!(listfile.contains("Arr/Units.dat"));
+    }
+}
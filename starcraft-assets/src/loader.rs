@@ -2,7 +2,7 @@ use super::*;
 use std::fmt;
 use std::io::Cursor;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum Tileset {
     Ashworld,
     Badlands,
@@ -15,7 +15,18 @@ pub enum Tileset {
 }
 
 impl Tileset {
-    fn file_name(&self) -> String {
+    pub const VALUES: [Tileset; 8] = [
+        Tileset::Ashworld,
+        Tileset::Badlands,
+        Tileset::Installation,
+        Tileset::Jungle,
+        Tileset::SpacePlatform,
+        Tileset::Desert,
+        Tileset::Arctic,
+        Tileset::Twilight,
+    ];
+
+    pub(crate) fn file_name(&self) -> String {
         match self {
             Tileset::Ashworld => "ashworld".into(),
             Tileset::Badlands => "badlands".into(),
@@ -92,15 +103,19 @@ impl<'a> AssetLoader<'a> {
 
     fn load_asset(&self, ext: EXT) -> Result<Vec<u8>> {
         let tileset_path = format!("tileset\\{}.{}", self.tileset.file_name(), ext.file_name());
-        let buf = self.fs.read(&tileset_path).map_err(|_| {
-            ErrorKind::AssetNotFound(self.tileset.file_name(), EXT::WPE.file_name())
-        })?;
-
-        Ok(buf)
+        self.fs.read(&tileset_path).map_err(|err| match err.kind() {
+            ErrorKind::Msg(_) => err
+                .chain_err(|| ErrorKind::AssetNotFound(self.tileset.file_name(), ext.file_name())),
+            _ => err,
+        })
     }
 }
 
 impl<'a> super::AssetLoader for AssetLoader<'a> {
+    fn tileset(&self) -> Tileset {
+        self.tileset
+    }
+
     fn load_cv5s(&self) -> std::result::Result<cv5::CV5s, errors::Error> {
         let buf = self.load_asset(EXT::CV5)?;
         let mut cursor = Cursor::new(&buf);
@@ -136,3 +151,46 @@ impl<'a> super::AssetLoader for AssetLoader<'a> {
         wpe::WPEs::from_buffer(&mut cursor).chain_err(|| "failed to load wpes")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FailingFileSystem;
+
+    impl super::super::fs::ReadOnlyFileSystem for FailingFileSystem {
+        fn read(&self, file_name: &str) -> Result<Vec<u8>> {
+            if file_name.ends_with(".cv5") {
+                Err(ErrorKind::Corrupt(file_name.into(), "StarDat.mpq".into(), Some(512)).into())
+            } else {
+                Err("missing".into())
+            }
+        }
+
+        fn exists(&self, file_name: &str) -> Result<bool> {
+            Ok(file_name.ends_with(".cv5"))
+        }
+
+        fn size(&self, _file_name: &str) -> Result<u64> {
+            Err("missing".into())
+        }
+    }
+
+    #[test]
+    fn test_asset_loader_passes_lookup_errors_through() {
+        let loader = AssetLoader::new(Tileset::Jungle, &FailingFileSystem);
+
+        match loader.load_asset(EXT::CV5).unwrap_err().kind() {
+            ErrorKind::Corrupt(file_name, archive, offset) => {
+                assert_eq!(file_name, "tileset\\jungle.cv5");
+                assert_eq!(archive, "StarDat.mpq");
+                assert_eq!(*offset, Some(512));
+            }
+            kind => panic!("unexpected error: {}", kind),
+        }
+        match loader.load_asset(EXT::VX4).unwrap_err().kind() {
+            ErrorKind::AssetNotFound(_, ext) => assert_eq!(ext, "vx4"),
+            kind => panic!("unexpected error: {}", kind),
+        }
+    }
+}
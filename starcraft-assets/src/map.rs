@@ -1,6 +1,8 @@
-use ceres_mpq;
 use std::io::{Cursor, Read};
 
+use super::cv5::CV5s;
+use super::mpq::{FileHash, MPQArchive};
+
 pub use super::chk;
 
 use super::errors::*;
@@ -15,45 +17,87 @@ pub struct Map {
     pub sides: Vec<chk::Side>,
     pub mega_tile_ids: Vec<chk::MegaTileID>,
     pub str_data: chk::StringData,
+    pub doodads: Vec<chk::Doodad>,
+    pub sprites: Vec<chk::Sprite>,
+    pub units: Vec<chk::Unit>,
+    /// Unit stats from `UNIx`, or from `UNIS` if the map has no Brood War settings
+    pub unit_settings: Option<chk::UnitSettings>,
+    /// Upgrade costs from `UPGx`, or from `UPGS`
+    pub upgrade_settings: Option<chk::UpgradeSettings>,
+    /// Technology costs from `TECx`, or from `TECS`
+    pub tech_settings: Option<chk::TechSettings>,
+}
+
+/// A sprite drawn over the terrain: one placed in THG2, or the overlay of a DD2 doodad.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Overlay {
+    pub sprite: chk::Sprite,
+    /// Whether the doodad's tile group draws the overlay mirrored
+    pub flipped: bool,
 }
 
 const MAP_FILE_NAME: &str = "staredit\\scenario.chk";
+const MAP_FILE_HASH: FileHash = FileHash::new(MAP_FILE_NAME);
 
 impl Map {
+    /// Reads a map from a `.scm` or `.scx` file.
+    ///
+    /// The scenario is looked up by its hash, so maps whose listfile is missing or faked
+    /// by a protector can still be read.
     pub fn from_mpq_file(file_name: &str) -> Result<Map> {
-        let mut mpq_file = std::fs::File::open(file_name)
-            .chain_err(|| format!("failed to open mpq file {}", file_name))?;
-
-        let mut buf: Vec<u8> = Vec::new();
-        mpq_file
-            .read_to_end(&mut buf)
-            .chain_err(|| format!("failed to read mpq file {}", file_name))?;
-
-        let mut cursor = Cursor::new(buf);
-        let mut archive =
-            ceres_mpq::Archive::open(&mut cursor).chain_err(|| "failed to open mpq archive")?;
-        let files = archive
-            .files()
-            .ok_or_else(|| "Starcraft maps must have a file inside")?;
-
-        if files
-            .into_iter()
-            .filter(|f| f == MAP_FILE_NAME)
-            .collect::<Vec<_>>()
-            .len()
-            == 0
-        {
-            let err_msg = format!("Starcraft maps must contain a {} file", MAP_FILE_NAME);
-            return Err(err_msg.into());
-        }
+        let archive = MPQArchive::open(file_name)?;
         let chunks = archive
-            .read_file(&MAP_FILE_NAME)
+            .read_file_by_hash(&MAP_FILE_HASH)
             .chain_err(|| format!("failed to read archive file {}", MAP_FILE_NAME))?;
 
         Map::from_chunks(&chunks)
     }
 
-    fn from_chunks(chunks: &Vec<u8>) -> Result<Map> {
+    /// The sprites drawn over the terrain, given the tileset's tile groups.
+    ///
+    /// Every doodad whose tile group has an overlay contributes it at the doodad's position.
+    /// THG2 usually places the same overlay again, so a THG2 sprite that matches a doodad's
+    /// overlay is not repeated. Disabled sprites and doodads are left out.
+    pub fn overlays(&self, cv5s: &CV5s) -> Vec<Overlay> {
+        let mut overlays = self
+            .doodads
+            .iter()
+            .filter_map(|doodad| {
+                let (_, group) = cv5s.find_doodad(doodad.id)?;
+                if !group.is_sprite_overlay() && !group.is_unit_overlay() {
+                    return None;
+                }
+
+                let sprite = chk::Sprite::new(
+                    group.overlay_id,
+                    doodad.x,
+                    doodad.y,
+                    doodad.owner,
+                    group.is_sprite_overlay(),
+                );
+                Some((doodad.disabled, sprite, group.is_overlay_flipped()))
+            })
+            .collect::<Vec<_>>();
+
+        for sprite in self.sprites.iter() {
+            let is_doodad_overlay = overlays.iter().any(|(_, overlay, _)| {
+                (overlay.id, overlay.x, overlay.y, overlay.is_pure_sprite())
+                    == (sprite.id, sprite.x, sprite.y, sprite.is_pure_sprite())
+            });
+            if !is_doodad_overlay {
+                overlays.push((sprite.is_disabled(), sprite.clone(), false));
+            }
+        }
+
+        overlays
+            .into_iter()
+            .filter(|(disabled, _, _)| !disabled)
+            .map(|(_, sprite, flipped)| Overlay { sprite, flipped })
+            .collect()
+    }
+
+    /// Reads a map from the contents of its `scenario.chk`
+    pub fn from_chunks(chunks: &Vec<u8>) -> Result<Map> {
         let mut cursor = std::io::Cursor::new(chunks);
 
         let mut scenario_type: Option<chk::ScenarioType> = None;
@@ -64,6 +108,15 @@ impl Map {
         let mut sides: Option<Vec<chk::Side>> = None;
         let mut mega_tile_ids: Option<Vec<chk::MegaTileID>> = None;
         let mut str_data: Option<chk::StringData> = None;
+        let mut doodads: Option<Vec<chk::Doodad>> = None;
+        let mut sprites: Option<Vec<chk::Sprite>> = None;
+        let mut units: Option<Vec<chk::Unit>> = None;
+        let mut unit_settings: Option<chk::UnitSettings> = None;
+        let mut brood_war_unit_settings: Option<chk::UnitSettings> = None;
+        let mut upgrade_settings: Option<chk::UpgradeSettings> = None;
+        let mut brood_war_upgrade_settings: Option<chk::UpgradeSettings> = None;
+        let mut tech_settings: Option<chk::TechSettings> = None;
+        let mut brood_war_tech_settings: Option<chk::TechSettings> = None;
 
         while cursor.get_ref().len() as u64 - cursor.position() > 0 {
             let chunk_header = chk::Header::from_buffer(&mut cursor)?;
@@ -85,6 +138,17 @@ impl Map {
                 Some(chk::Chunk::Sides(a)) => sides = Some(a),
                 Some(chk::Chunk::MegaTileIDs(a)) => mega_tile_ids = Some(a),
                 Some(chk::Chunk::StringData(a)) => str_data = Some(a),
+                Some(chk::Chunk::Doodads(a)) => doodads = Some(a),
+                Some(chk::Chunk::Sprites(a)) => sprites = Some(a),
+                Some(chk::Chunk::Units(a)) => units = Some(a),
+                Some(chk::Chunk::UnitSettings(a)) => unit_settings = Some(a),
+                Some(chk::Chunk::BroodWarUnitSettings(a)) => brood_war_unit_settings = Some(a),
+                Some(chk::Chunk::UpgradeSettings(a)) => upgrade_settings = Some(a),
+                Some(chk::Chunk::BroodWarUpgradeSettings(a)) => {
+                    brood_war_upgrade_settings = Some(a)
+                }
+                Some(chk::Chunk::TechSettings(a)) => tech_settings = Some(a),
+                Some(chk::Chunk::BroodWarTechSettings(a)) => brood_war_tech_settings = Some(a),
                 None => {}
             };
         }
@@ -98,6 +162,12 @@ impl Map {
             sides: sides.ok_or("side is required")?,
             mega_tile_ids: mega_tile_ids.ok_or("megaTileIds is required")?,
             str_data: str_data.ok_or("string data is required")?,
+            doodads: doodads.unwrap_or_default(),
+            sprites: sprites.unwrap_or_default(),
+            units: units.unwrap_or_default(),
+            unit_settings: brood_war_unit_settings.or(unit_settings),
+            upgrade_settings: brood_war_upgrade_settings.or(upgrade_settings),
+            tech_settings: brood_war_tech_settings.or(tech_settings),
         })
     }
 }
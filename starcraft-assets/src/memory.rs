@@ -0,0 +1,102 @@
+use super::errors::*;
+use super::fs::ReadOnlyFileSystem;
+use std::collections::HashMap;
+
+/// A read-only file system over files held in memory, for tests and tools that build
+/// their assets on the fly.
+///
+/// Paths are matched like MPQ paths, ignoring ASCII case and treating `/` like `\`.
+pub struct MemoryFileSystem {
+    name: String,
+    /// File names and contents by normalized path
+    files: HashMap<String, (String, Vec<u8>)>,
+}
+
+impl MemoryFileSystem {
+    pub fn new(name: impl Into<String>) -> MemoryFileSystem {
+        MemoryFileSystem {
+            name: name.into(),
+            files: HashMap::new(),
+        }
+    }
+
+    /// Adds a file, replacing any file with the same path
+    pub fn insert(&mut self, file_name: impl Into<String>, data: impl Into<Vec<u8>>) {
+        let file_name = file_name.into();
+        self.files
+            .insert(normalize(&file_name), (file_name, data.into()));
+    }
+
+    pub fn remove(&mut self, file_name: &str) -> Option<Vec<u8>> {
+        self.files
+            .remove(&normalize(file_name))
+            .map(|(_, data)| data)
+    }
+
+    fn get(&self, file_name: &str) -> Result<&[u8]> {
+        self.files
+            .get(&normalize(file_name))
+            .map(|(_, data)| data.as_slice())
+            .ok_or_else(|| ErrorKind::NotFound(file_name.into(), self.name.clone()).into())
+    }
+}
+
+fn normalize(file_name: &str) -> String {
+    file_name.replace('/', "\\").to_ascii_lowercase()
+}
+
+impl ReadOnlyFileSystem for MemoryFileSystem {
+    fn read(&self, file_name: &str) -> Result<Vec<u8>> {
+        self.get(file_name).map(|data| data.to_vec())
+    }
+
+    fn files(&self) -> Result<Vec<String>> {
+        let mut files = self
+            .files
+            .values()
+            .map(|(file_name, _)| file_name.clone())
+            .collect::<Vec<_>>();
+        files.sort_by_key(|file_name| normalize(file_name));
+
+        Ok(files)
+    }
+
+    fn exists(&self, file_name: &str) -> Result<bool> {
+        Ok(self.files.contains_key(&normalize(file_name)))
+    }
+
+    fn size(&self, file_name: &str) -> Result<u64> {
+        self.get(file_name).map(|data| data.len() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_memory_file_system_matches_mpq_paths() {
+        let mut fs = MemoryFileSystem::new("memory");
+        fs.insert("unit\\zerg\\Zergling.grp", vec![1, 2, 3]);
+        fs.insert("arr/units.dat", &b"units"[..]);
+
+        assert_eq!(fs.read("UNIT/ZERG/zergling.grp").unwrap(), vec![1, 2, 3]);
+        assert_eq!(
+            fs.files().unwrap(),
+            vec!["arr/units.dat", "unit\\zerg\\Zergling.grp"]
+        );
+        assert_eq!(fs.size("arr\\units.dat").unwrap(), 5);
+
+        let mut units = String::new();
+        fs.open("arr\\units.dat")
+            .unwrap()
+            .read_to_string(&mut units)
+            .unwrap();
+        assert_eq!(units, "units");
+
+        assert_eq!(fs.remove("ARR\\UNITS.DAT"), Some(b"units".to_vec()));
+        assert!(!fs.exists("arr\\units.dat").unwrap());
+        assert!(fs.read("arr\\units.dat").unwrap_err().is_not_found());
+    }
+}
@@ -0,0 +1,99 @@
+use super::compression::invalid_data;
+use byteorder::{ByteOrder, LittleEndian};
+use std::io;
+
+const INITIAL_STEP_INDEX: usize = 0x2C;
+const MAX_STEP_INDEX: usize = 88;
+
+const NEXT_STEP_INDEX: [isize; 32] = [
+    -1, 0, -1, 4, -1, 2, -1, 6, -1, 1, -1, 5, -1, 3, -1, 7, -1, 1, -1, 5, -1, 3, -1, 7, -1, 2, -1,
+    4, -1, 6, -1, 8,
+];
+
+const STEP_SIZES: [i32; MAX_STEP_INDEX + 1] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66,
+    73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449,
+    494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272,
+    2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493,
+    10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+/// Decompresses Storm's ADPCM into at most `size` bytes of 16-bit PCM, with `channels`
+/// interleaved channels.
+pub fn decompress(input: &[u8], channels: usize, size: usize) -> io::Result<Vec<u8>> {
+    if input.len() < 2 + channels * 2 {
+        return Err(invalid_data("ADPCM stream is too short"));
+    }
+
+    let mut output = Vec::with_capacity(size);
+    let write = |output: &mut Vec<u8>, sample: i32| {
+        if output.len() + 2 > size {
+            return false;
+        }
+        output.extend_from_slice(&(sample as i16).to_le_bytes());
+        true
+    };
+
+    // the first byte is unused, the second is the compression level
+    let shift = input[1] as u32;
+    let mut predicted = [0i32; 2];
+    let mut step_index = [INITIAL_STEP_INDEX; 2];
+    for (channel, sample) in input[2..2 + channels * 2].chunks_exact(2).enumerate() {
+        predicted[channel] = LittleEndian::read_i16(sample) as i32;
+        if !write(&mut output, predicted[channel]) {
+            return Ok(output);
+        }
+    }
+
+    let mut channel = channels - 1;
+    for encoded in &input[2 + channels * 2..] {
+        channel = (channel + 1) % channels;
+
+        if encoded & 0x80 == 0 {
+            let step = STEP_SIZES[step_index[channel]];
+            let mut difference = step.checked_shr(shift).unwrap_or(0);
+            for bit in 0..6 {
+                if encoded & (1 << bit) != 0 {
+                    difference += step >> bit;
+                }
+            }
+
+            predicted[channel] = if encoded & 0x40 != 0 {
+                (predicted[channel] - difference).max(-32768)
+            } else {
+                (predicted[channel] + difference).min(32767)
+            };
+            if !write(&mut output, predicted[channel]) {
+                break;
+            }
+
+            step_index[channel] = (step_index[channel] as isize
+                + NEXT_STEP_INDEX[(encoded & 0x1F) as usize])
+                .max(0)
+                .min(MAX_STEP_INDEX as isize) as usize;
+            continue;
+        }
+
+        match encoded & 0x7F {
+            // repeat the previous sample
+            0 => {
+                step_index[channel] = step_index[channel].saturating_sub(1);
+                if !write(&mut output, predicted[channel]) {
+                    break;
+                }
+            }
+            // the next three stay on the same channel
+            1 => {
+                step_index[channel] = (step_index[channel] + 8).min(MAX_STEP_INDEX);
+                channel = (channel + 1) % channels;
+            }
+            2 => channel = (channel + 1) % channels,
+            _ => {
+                step_index[channel] = step_index[channel].saturating_sub(8);
+                channel = (channel + 1) % channels;
+            }
+        }
+    }
+
+    Ok(output)
+}
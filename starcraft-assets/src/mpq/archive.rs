@@ -0,0 +1,1108 @@
+use super::super::errors::*;
+use super::super::fs::ReadSeek;
+use super::compression;
+use super::crypto::{self, HashType};
+use super::explode;
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt};
+use memmap2::Mmap;
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Mutex;
+
+const HEADER_SIGNATURE: u32 = 0x1A51_504D;
+const USER_DATA_SIGNATURE: u32 = 0x1B51_504D;
+/// Archives can be embedded in other files, such as installers, at any multiple of this
+const HEADER_ALIGNMENT: u64 = 0x200;
+const HEADER_SIZE_V1: u64 = 0x20;
+const HEADER_SIZE_V2: u32 = 0x2C;
+const FORMAT_VERSION_2: u16 = 1;
+
+const HASH_TABLE_KEY: &str = "(hash table)";
+const BLOCK_TABLE_KEY: &str = "(block table)";
+const TABLE_ENTRY_SIZE: usize = 16;
+
+const HASH_ENTRY_EMPTY: u32 = 0xFFFF_FFFF;
+/// Files that archives keep about themselves
+const INTERNAL_FILE_NAMES: [&str; 3] = ["(listfile)", "(attributes)", "(signature)"];
+const LOCALE_NEUTRAL: u16 = 0;
+
+/// Where an archive starts in its file and where its tables are.
+#[derive(Debug, Clone)]
+pub struct Header {
+    /// Offset of the archive in its file; every other offset is relative to it
+    pub offset: u64,
+    /// 0 for the original format, 1 for the Burning Crusade format
+    pub format_version: u16,
+    pub sector_size: usize,
+    pub hash_table_offset: u64,
+    pub hash_table_entries: u32,
+    pub block_table_offset: u64,
+    pub block_table_entries: u32,
+    /// Upper 16 bits of each block offset, for archives over 4 GiB
+    pub hi_block_table_offset: Option<u64>,
+}
+
+impl Header {
+    /// Finds the archive header in `reader`, skipping any data in front of it
+    fn find<R: Read + Seek>(reader: &mut R, len: u64, archive: &str) -> Result<Header> {
+        let mut offset = 0;
+        while offset + HEADER_SIZE_V1 <= len {
+            let mut read_signature = |offset: u64| -> io::Result<(u32, u32, u32)> {
+                reader.seek(SeekFrom::Start(offset))?;
+                let mut buf = [0u8; 12];
+                reader.read_exact(&mut buf)?;
+                Ok((
+                    LittleEndian::read_u32(&buf[0..]),
+                    LittleEndian::read_u32(&buf[4..]),
+                    LittleEndian::read_u32(&buf[8..]),
+                ))
+            };
+
+            let (signature, _, user_data_header_offset) = read_signature(offset)
+                .chain_err(|| ErrorKind::IncorrectFileFormat(offset, archive.into()))?;
+            match signature {
+                HEADER_SIGNATURE => return Header::from_reader(reader, offset, archive),
+                USER_DATA_SIGNATURE => {
+                    let header_offset = offset + user_data_header_offset as u64;
+                    if header_offset + HEADER_SIZE_V1 <= len
+                        && read_signature(header_offset)
+                            .map(|(signature, ..)| signature)
+                            .ok()
+                            == Some(HEADER_SIGNATURE)
+                    {
+                        return Header::from_reader(reader, header_offset, archive);
+                    }
+                }
+                _ => {}
+            }
+
+            offset += HEADER_ALIGNMENT;
+        }
+
+        Err(Error::with_chain(
+            Error::from("no MPQ header found"),
+            ErrorKind::IncorrectFileFormat(0, archive.into()),
+        ))
+    }
+
+    fn from_reader<R: Read + Seek>(reader: &mut R, offset: u64, archive: &str) -> Result<Header> {
+        (|| -> io::Result<Header> {
+            reader.seek(SeekFrom::Start(offset + 4))?;
+            let header_size = reader.read_u32::<LittleEndian>()?;
+            let _archive_size = reader.read_u32::<LittleEndian>()?;
+            let format_version = reader.read_u16::<LittleEndian>()?;
+            let sector_size_shift = reader.read_u16::<LittleEndian>()?;
+            let hash_table_offset = reader.read_u32::<LittleEndian>()? as u64;
+            let block_table_offset = reader.read_u32::<LittleEndian>()? as u64;
+            let hash_table_entries = reader.read_u32::<LittleEndian>()?;
+            let block_table_entries = reader.read_u32::<LittleEndian>()?;
+
+            let mut header = Header {
+                offset,
+                format_version,
+                sector_size: 0x200usize
+                    .checked_shl(sector_size_shift as u32)
+                    .filter(|size| *size <= 1 << 24)
+                    .ok_or_else(|| compression::invalid_data("invalid sector size"))?,
+                hash_table_offset: offset + hash_table_offset,
+                hash_table_entries,
+                block_table_offset: offset + block_table_offset,
+                block_table_entries,
+                hi_block_table_offset: None,
+            };
+
+            // StarCraft reads every archive as the original format, and protected maps fill
+            // the rest of the header with garbage, so only trust a consistent v2 header
+            if format_version == FORMAT_VERSION_2 && header_size >= HEADER_SIZE_V2 {
+                let hi_block_table_offset = reader.read_u64::<LittleEndian>()?;
+                let hash_table_offset_hi = reader.read_u16::<LittleEndian>()? as u64;
+                let block_table_offset_hi = reader.read_u16::<LittleEndian>()? as u64;
+
+                header.hash_table_offset += hash_table_offset_hi << 32;
+                header.block_table_offset += block_table_offset_hi << 32;
+                if hi_block_table_offset != 0 {
+                    header.hi_block_table_offset = Some(offset + hi_block_table_offset);
+                }
+            }
+
+            Ok(header)
+        })()
+        .chain_err(|| ErrorKind::IncorrectFileFormat(offset, archive.into()))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct HashEntry {
+    name_a: u32,
+    name_b: u32,
+    locale: u16,
+    block_index: u32,
+}
+
+/// An entry of the block table, describing how a file is stored.
+#[derive(Debug, Clone, Copy)]
+pub struct Block {
+    /// Offset of the file data, relative to the start of the archive
+    pub offset: u64,
+    pub packed_size: u32,
+    pub size: u32,
+    pub flags: u32,
+}
+
+impl Block {
+    pub const IMPLODE: u32 = 0x0000_0100;
+    pub const COMPRESS: u32 = 0x0000_0200;
+    pub const ENCRYPTED: u32 = 0x0001_0000;
+    /// The encryption key depends on where the file is stored
+    pub const FIX_KEY: u32 = 0x0002_0000;
+    pub const SINGLE_UNIT: u32 = 0x0100_0000;
+    pub const DELETE_MARKER: u32 = 0x0200_0000;
+    pub const SECTOR_CRC: u32 = 0x0400_0000;
+    pub const EXISTS: u32 = 0x8000_0000;
+
+    pub fn exists(&self) -> bool {
+        self.flags & Block::EXISTS != 0 && self.flags & Block::DELETE_MARKER == 0
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.flags & (Block::IMPLODE | Block::COMPRESS) != 0
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.flags & Block::ENCRYPTED != 0
+    }
+
+    pub fn is_single_unit(&self) -> bool {
+        self.flags & Block::SINGLE_UNIT != 0
+    }
+
+    /// Encryption key of the file stored in this block under `file_name`
+    pub fn key(&self, file_name: &str) -> u32 {
+        self.fix_key(crypto::file_key(file_name))
+    }
+
+    /// Adjusts a file key for where the file is stored, if the block asks for it
+    pub fn fix_key(&self, key: u32) -> u32 {
+        if self.flags & Block::FIX_KEY != 0 {
+            key.wrapping_add(self.offset as u32) ^ self.size
+        } else {
+            key
+        }
+    }
+
+    fn decompress(&self, buf: &[u8], size: usize) -> io::Result<Vec<u8>> {
+        if self.flags & Block::IMPLODE != 0 {
+            explode::explode(buf, size)
+        } else {
+            compression::decompress(buf, size)
+        }
+    }
+}
+
+/// The hashes a file is looked up and decrypted with, which can be computed ahead of time
+/// for files whose names are known but may be missing from the listfile.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct FileHash {
+    pub table_offset: u32,
+    pub name_a: u32,
+    pub name_b: u32,
+    /// Encryption key before any position adjustment
+    pub key: u32,
+}
+
+impl FileHash {
+    pub const fn new(file_name: &str) -> FileHash {
+        FileHash {
+            table_offset: crypto::hash_string(file_name, HashType::TableOffset),
+            name_a: crypto::hash_string(file_name, HashType::NameA),
+            name_b: crypto::hash_string(file_name, HashType::NameB),
+            key: crypto::file_key(file_name),
+        }
+    }
+}
+
+impl fmt::Display for FileHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:08X}:{:08X}", self.name_a, self.name_b)
+    }
+}
+
+/// Where an archive reads its blocks from.
+pub trait ArchiveSource {
+    /// Reads `len` bytes at `offset`, borrowing them if the archive is already in memory
+    fn read_at(&self, offset: u64, len: usize) -> io::Result<Cow<'_, [u8]>>;
+}
+
+/// A reader shared between threads, which seeks and copies for every read
+impl<R> ArchiveSource for Mutex<R>
+where
+    R: Read + Seek,
+{
+    fn read_at(&self, offset: u64, len: usize) -> io::Result<Cow<'_, [u8]>> {
+        let mut reader = self
+            .lock()
+            .map_err(|_| io::Error::other("archive reader is poisoned"))?;
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut buf = vec![0u8; len];
+        reader.read_exact(&mut buf)?;
+
+        Ok(Cow::Owned(buf))
+    }
+}
+
+impl ArchiveSource for Mmap {
+    fn read_at(&self, offset: u64, len: usize) -> io::Result<Cow<'_, [u8]>> {
+        usize::try_from(offset)
+            .ok()
+            .and_then(|start| self.get(start..start.checked_add(len)?))
+            .map(Cow::Borrowed)
+            .ok_or_else(|| io::ErrorKind::UnexpectedEof.into())
+    }
+}
+
+/// A MPQ archive, read with the native reader.
+///
+/// Supports the original and Burning Crusade formats, encrypted files, and every
+/// compression used by StarCraft's archives and maps.
+pub struct MPQArchive<S = Mutex<File>> {
+    name: String,
+    source: S,
+    pub(super) len: u64,
+    header: Header,
+    hash_table: Vec<HashEntry>,
+    blocks: Vec<Block>,
+}
+
+impl MPQArchive<Mutex<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<MPQArchive<Mutex<File>>> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).chain_err(|| format!("failed to open mpq file {}", path.display()))?;
+
+        MPQArchive::from_reader(path.display().to_string(), file)
+    }
+}
+
+impl<R> MPQArchive<Mutex<R>>
+where
+    R: Read + Seek,
+{
+    /// Reads the archive in `reader`, naming it `name` in errors
+    pub fn from_reader(name: impl Into<String>, reader: R) -> Result<MPQArchive<Mutex<R>>> {
+        MPQArchive::load(name.into(), reader, Mutex::new)
+    }
+}
+
+/// An archive read through a memory map, so sectors are decoded straight from the mapped
+/// pages without a lock or a copy.
+pub type MappedMPQArchive = MPQArchive<Mmap>;
+
+impl MPQArchive<Mmap> {
+    /// Maps the archive at `path` into memory.
+    ///
+    /// Nothing, in this process or another, may write to or truncate the file while it is
+    /// mapped: writes change data under decoded sectors and truncation crashes the reader.
+    /// Map installed game data such as `StarDat.mpq`, and open files an editor may rewrite,
+    /// such as maps, with `MPQArchive::open` instead.
+    pub fn map(path: impl AsRef<Path>) -> Result<MappedMPQArchive> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).chain_err(|| format!("failed to open mpq file {}", path.display()))?;
+        // sound as long as nothing modifies the file, which the doc above leaves to callers
+        let mmap = unsafe { Mmap::map(&file) }
+            .chain_err(|| format!("failed to map mpq file {}", path.display()))?;
+
+        MPQArchive::load(
+            path.display().to_string(),
+            Cursor::new(mmap),
+            Cursor::into_inner,
+        )
+    }
+}
+
+impl<S> MPQArchive<S>
+where
+    S: ArchiveSource,
+{
+    /// Reads the tables of the archive in `reader`, naming it `name` in errors, then keeps
+    /// `reader` as the source of its blocks
+    fn load<R: Read + Seek>(
+        name: String,
+        mut reader: R,
+        into_source: impl FnOnce(R) -> S,
+    ) -> Result<MPQArchive<S>> {
+        let len = reader
+            .seek(SeekFrom::End(0))
+            .chain_err(|| ErrorKind::IncorrectFileFormat(0, name.clone()))?;
+        let header = Header::find(&mut reader, len, &name)?;
+
+        let hash_table = read_table(
+            &mut reader,
+            len,
+            header.hash_table_offset,
+            header.hash_table_entries,
+            HASH_TABLE_KEY,
+            &name,
+        )?
+        .chunks_exact(TABLE_ENTRY_SIZE)
+        .map(|entry| HashEntry {
+            name_a: LittleEndian::read_u32(&entry[0..]),
+            name_b: LittleEndian::read_u32(&entry[4..]),
+            locale: LittleEndian::read_u16(&entry[8..]),
+            block_index: LittleEndian::read_u32(&entry[12..]),
+        })
+        .collect::<Vec<_>>();
+
+        let mut blocks = read_table(
+            &mut reader,
+            len,
+            header.block_table_offset,
+            header.block_table_entries,
+            BLOCK_TABLE_KEY,
+            &name,
+        )?
+        .chunks_exact(TABLE_ENTRY_SIZE)
+        .map(|entry| Block {
+            offset: LittleEndian::read_u32(&entry[0..]) as u64,
+            packed_size: LittleEndian::read_u32(&entry[4..]),
+            size: LittleEndian::read_u32(&entry[8..]),
+            flags: LittleEndian::read_u32(&entry[12..]),
+        })
+        .collect::<Vec<_>>();
+
+        if let Some(offset) = header.hi_block_table_offset {
+            let mut buf = vec![0u8; blocks.len() * 2];
+            reader
+                .seek(SeekFrom::Start(offset))
+                .and_then(|_| reader.read_exact(&mut buf))
+                .chain_err(|| ErrorKind::IncorrectFileFormat(offset, name.clone()))?;
+            for (block, hi) in blocks.iter_mut().zip(buf.chunks_exact(2)) {
+                block.offset |= (LittleEndian::read_u16(hi) as u64) << 32;
+            }
+        }
+
+        Ok(MPQArchive {
+            name,
+            source: into_source(reader),
+            len,
+            header,
+            hash_table,
+            blocks,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    /// The block holding `file_name`, preferring the locale neutral version
+    pub fn find_block(&self, file_name: &str) -> Option<&Block> {
+        self.find_block_by_hash(&FileHash::new(file_name))
+    }
+
+    pub fn find_block_by_hash(&self, hash: &FileHash) -> Option<&Block> {
+        self.find_block_index(hash).map(|index| &self.blocks[index])
+    }
+
+    /// Probes the hash table the way Storm does, from the declared size rather than the
+    /// entries that could be read. Slots past the end of a truncated table are empty.
+    fn find_block_index(&self, hash: &FileHash) -> Option<usize> {
+        let mask = self.header.hash_table_entries.checked_sub(1)? as usize;
+        let mut found = None;
+        for i in 0..=mask {
+            let slot = (hash.table_offset as usize).wrapping_add(i) & mask;
+            let entry = match self.hash_table.get(slot) {
+                Some(entry) if entry.block_index != HASH_ENTRY_EMPTY => entry,
+                _ => break,
+            };
+            if entry.name_a != hash.name_a || entry.name_b != hash.name_b {
+                continue;
+            }
+
+            let index = entry.block_index as usize;
+            match self.blocks.get(index) {
+                Some(block) if block.exists() && entry.locale == LOCALE_NEUTRAL => {
+                    return Some(index)
+                }
+                Some(block) if block.exists() => found = found.or(Some(index)),
+                _ => {}
+            }
+        }
+
+        found
+    }
+
+    pub fn read_file(&self, file_name: &str) -> Result<Vec<u8>> {
+        let block = self
+            .find_block(file_name)
+            .ok_or_else(|| ErrorKind::NotFound(file_name.into(), self.name.clone()))?;
+
+        self.read_block(file_name, block, Some(block.key(file_name)))
+    }
+
+    /// Reads a file by its precomputed hash, whether or not its name is listed anywhere
+    pub fn read_file_by_hash(&self, hash: &FileHash) -> Result<Vec<u8>> {
+        let block = self
+            .find_block_by_hash(hash)
+            .ok_or_else(|| ErrorKind::NotFound(hash.to_string(), self.name.clone()))?;
+
+        self.read_block(&hash.to_string(), block, Some(block.fix_key(hash.key)))
+    }
+
+    /// The name of each block that `file_names`, the archive's own listfile or its internal
+    /// files resolve to
+    pub fn block_names<'a>(
+        &self,
+        file_names: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<Option<String>> {
+        let listfile = super::ReadOnlyMPQArchive::files(self).unwrap_or_default();
+        let mut names = vec![None; self.blocks.len()];
+        let mut name = |file_name: &str| {
+            if let Some(index) = self.find_block_index(&FileHash::new(file_name)) {
+                names[index].get_or_insert_with(|| file_name.to_string());
+            }
+        };
+        listfile.iter().for_each(|file_name| name(file_name));
+        INTERNAL_FILE_NAMES
+            .iter()
+            .for_each(|file_name| name(file_name));
+        file_names.into_iter().for_each(name);
+
+        names
+    }
+
+    /// Indices of the blocks in the hash table that neither `file_names` nor the archive's
+    /// own listfile resolve to
+    pub fn unnamed_blocks<'a>(&self, file_names: impl IntoIterator<Item = &'a str>) -> Vec<usize> {
+        let names = self.block_names(file_names);
+        let mut unnamed = self
+            .hash_table
+            .iter()
+            .map(|entry| entry.block_index as usize)
+            .filter(|index| {
+                self.blocks
+                    .get(*index)
+                    .filter(|block| block.exists())
+                    .is_some()
+                    && names[*index].is_none()
+            })
+            .collect::<Vec<_>>();
+        unnamed.sort_unstable();
+        unnamed.dedup();
+
+        unnamed
+    }
+
+    /// Reads a block without knowing the name of its file.
+    ///
+    /// The key of an encrypted file is brute-forced from its sector offset table, which only
+    /// works for compressed files split into sectors; other encrypted files fail with
+    /// `ErrorKind::Encrypted`.
+    pub fn recover_block(&self, index: usize) -> Result<Vec<u8>> {
+        let label = format!("block {}", index);
+        let block = self
+            .blocks
+            .get(index)
+            .filter(|block| block.exists())
+            .ok_or_else(|| ErrorKind::NotFound(label.clone(), self.name.clone()))?;
+
+        self.read_block(&label, block, None)
+    }
+
+    /// Reads the file in `block`, detecting the key of an encrypted file if `key` is unknown.
+    /// `file_name` only names the file in errors.
+    fn read_block(&self, file_name: &str, block: &Block, key: Option<u32>) -> Result<Vec<u8>> {
+        let offset = self.header.offset + block.offset;
+        let corrupt =
+            |offset: u64| ErrorKind::Corrupt(file_name.into(), self.name.clone(), Some(offset));
+        if offset + block.packed_size as u64 > self.len {
+            return Err(corrupt(offset).into());
+        }
+
+        let raw = self
+            .read_at(offset, block.packed_size as usize)
+            .chain_err(|| ErrorKind::Io(file_name.into(), self.name.clone(), Some(offset)))?;
+        let size = block.size as usize;
+        let key = match key {
+            _ if !block.is_encrypted() => 0,
+            Some(key) => key,
+            None => self.detect_key(block, &raw).ok_or_else(|| {
+                ErrorKind::Encrypted(file_name.into(), self.name.clone(), Some(offset))
+            })?,
+        };
+
+        if block.is_single_unit() {
+            return self
+                .decode_sector(block, &raw, 0, key, size)
+                .map(Cow::into_owned)
+                .chain_err(|| corrupt(offset));
+        }
+
+        let sector_size = self.header.sector_size;
+        let sector_count = size.div_ceil(sector_size);
+        let mut data = Vec::with_capacity(size);
+
+        if !block.is_compressed() {
+            if raw.len() < size {
+                return Err(corrupt(offset).into());
+            }
+            for (i, sector) in raw[..size].chunks(sector_size).enumerate() {
+                let sector = self
+                    .decode_sector(block, sector, i, key, sector.len())
+                    .chain_err(|| corrupt(offset))?;
+                data.extend_from_slice(&sector);
+            }
+            return Ok(data);
+        }
+
+        let table_entries = self.sector_table_entries(block);
+        if raw.len() < table_entries * 4 {
+            return Err(corrupt(offset).into());
+        }
+        let sector_offsets =
+            self.sector_offsets(file_name, block, &raw[..table_entries * 4], raw.len(), key)?;
+        for i in 0..sector_count {
+            let (start, end) = (sector_offsets[i], sector_offsets[i + 1]);
+            let expected = sector_size.min(size - i * sector_size);
+            let sector = self
+                .decode_sector(block, &raw[start..end], i, key, expected)
+                .chain_err(|| corrupt(offset + start as u64))?;
+            data.extend_from_slice(&sector);
+        }
+
+        Ok(data)
+    }
+
+    /// Decrypts a sector offset table and checks that every sector lies within the
+    /// `packed_size` bytes of the block
+    pub(super) fn sector_offsets(
+        &self,
+        file_name: &str,
+        block: &Block,
+        table: &[u8],
+        packed_size: usize,
+        key: u32,
+    ) -> Result<Vec<usize>> {
+        let mut table = table.to_vec();
+        if block.is_encrypted() {
+            crypto::decrypt(&mut table, key.wrapping_sub(1));
+        }
+        let sector_offsets = table
+            .chunks_exact(4)
+            .map(|entry| LittleEndian::read_u32(entry) as usize)
+            .collect::<Vec<_>>();
+
+        let sector_count = (block.size as usize).div_ceil(self.header.sector_size);
+        let valid = sector_offsets[..=sector_count]
+            .windows(2)
+            .all(|bounds| bounds[0] <= bounds[1] && bounds[1] <= packed_size);
+        if valid {
+            return Ok(sector_offsets);
+        }
+
+        let offset = Some(self.header.offset + block.offset);
+        // a sector table that makes no sense usually means the wrong key
+        Err(if block.is_encrypted() {
+            ErrorKind::Encrypted(file_name.into(), self.name.clone(), offset).into()
+        } else {
+            ErrorKind::Corrupt(file_name.into(), self.name.clone(), offset).into()
+        })
+    }
+
+    /// Decrypts and decompresses sector `index` of a file into `size` bytes. A single unit
+    /// file is one sector. A plain sector is returned as it was read.
+    pub(super) fn decode_sector<'s>(
+        &self,
+        block: &Block,
+        sector: &'s [u8],
+        index: usize,
+        key: u32,
+        size: usize,
+    ) -> io::Result<Cow<'s, [u8]>> {
+        let mut sector = Cow::Borrowed(sector);
+        if block.is_encrypted() {
+            crypto::decrypt(sector.to_mut(), key.wrapping_add(index as u32));
+        }
+        if block.is_compressed() && sector.len() < size {
+            sector = Cow::Owned(block.decompress(&sector, size)?);
+        }
+        if sector.len() < size {
+            return Err(compression::invalid_data(format!(
+                "sector {} holds {} of its {} bytes",
+                index,
+                sector.len(),
+                size
+            )));
+        }
+
+        Ok(match sector {
+            Cow::Borrowed(sector) => Cow::Borrowed(&sector[..size]),
+            Cow::Owned(mut sector) => {
+                sector.truncate(size);
+                Cow::Owned(sector)
+            }
+        })
+    }
+
+    /// Brute-forces the key of a compressed file from the known first entry of its sector
+    /// offset table
+    fn detect_key(&self, block: &Block, raw: &[u8]) -> Option<u32> {
+        if !block.is_compressed() || block.is_single_unit() {
+            return None;
+        }
+
+        let table_size = self.sector_table_entries(block) as u32 * 4;
+        crypto::detect_key(raw, table_size, table_size + self.header.sector_size as u32)
+            .map(|table_key| table_key.wrapping_add(1))
+    }
+
+    pub(super) fn sector_table_entries(&self, block: &Block) -> usize {
+        (block.size as usize).div_ceil(self.header.sector_size)
+            + 1
+            + (block.flags & Block::SECTOR_CRC != 0) as usize
+    }
+
+    pub(super) fn read_at(&self, offset: u64, len: usize) -> io::Result<Cow<'_, [u8]>> {
+        self.source.read_at(offset, len)
+    }
+}
+
+/// Reads and decrypts a hash or block table. Protected maps declare more entries than
+/// the file holds, so only the entries that fit are read.
+fn read_table<R: Read + Seek>(
+    reader: &mut R,
+    len: u64,
+    offset: u64,
+    entries: u32,
+    key_name: &str,
+    archive: &str,
+) -> Result<Vec<u8>> {
+    let available = len.saturating_sub(offset) / TABLE_ENTRY_SIZE as u64;
+    let mut buf = vec![0u8; (entries as u64).min(available) as usize * TABLE_ENTRY_SIZE];
+    reader
+        .seek(SeekFrom::Start(offset))
+        .and_then(|_| reader.read_exact(&mut buf))
+        .chain_err(|| ErrorKind::IncorrectFileFormat(offset, archive.into()))?;
+    crypto::decrypt(&mut buf, crypto::hash_string(key_name, HashType::FileKey));
+
+    Ok(buf)
+}
+
+impl<S> super::ReadOnlyMPQArchive for MPQArchive<S>
+where
+    S: ArchiveSource,
+{
+    fn read_file(&self, file_name: &str) -> Result<Vec<u8>> {
+        MPQArchive::read_file(self, file_name)
+    }
+
+    fn open_file(&self, file_name: &str) -> Result<Box<dyn ReadSeek + '_>> {
+        Ok(Box::new(MPQArchive::open_file(self, file_name)?))
+    }
+
+    fn exists(&self, file_name: &str) -> Result<bool> {
+        Ok(self.find_block(file_name).is_some())
+    }
+
+    fn size(&self, file_name: &str) -> Result<u64> {
+        self.find_block(file_name)
+            .map(|block| block.size as u64)
+            .ok_or_else(|| ErrorKind::NotFound(file_name.into(), self.name.clone()).into())
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::super::ReadOnlyMPQArchive;
+    use super::*;
+    use std::io::Write;
+
+    pub struct TestFile {
+        pub name: &'static str,
+        pub data: Vec<u8>,
+        pub flags: u32,
+    }
+
+    /// Builds an archive behind 0x200 bytes of other data, with 512 byte sectors and
+    /// room for a v2 header
+    pub fn build_archive(files: &[TestFile]) -> Vec<u8> {
+        build_archive_with(files, |_, sector| {
+            let compressed = zlib_sector(sector);
+            if compressed.len() < sector.len() {
+                compressed
+            } else {
+                sector.to_vec()
+            }
+        })
+    }
+
+    pub fn zlib_sector(sector: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::ZlibEncoder::new(vec![2], flate2::Compression::best());
+        encoder.write_all(sector).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// Builds an archive like `build_archive`, packing sector `index` of a compressed file
+    /// with `compress(index, sector)`
+    pub fn build_archive_with(
+        files: &[TestFile],
+        compress: impl Fn(usize, &[u8]) -> Vec<u8>,
+    ) -> Vec<u8> {
+        let archive_offset = HEADER_ALIGNMENT as usize;
+        let hash_table_entries = 8;
+        let mut archive = vec![0u8; HEADER_SIZE_V2 as usize];
+        let mut hash_table = vec![0xFFu8; hash_table_entries * TABLE_ENTRY_SIZE];
+        let mut block_table = Vec::new();
+
+        for (block_index, file) in files.iter().enumerate() {
+            let mut block = Block {
+                offset: archive.len() as u64,
+                packed_size: 0,
+                size: file.data.len() as u32,
+                flags: file.flags | Block::EXISTS,
+            };
+            let key = block.key(file.name);
+
+            let mut sectors = file
+                .data
+                .chunks(0x200)
+                .enumerate()
+                .map(|(index, sector)| {
+                    if block.is_compressed() {
+                        compress(index, sector)
+                    } else {
+                        sector.to_vec()
+                    }
+                })
+                .collect::<Vec<_>>();
+            let mut packed = Vec::new();
+            if block.is_compressed() {
+                let mut table = Vec::new();
+                let mut sector_offset = (sectors.len() + 1) * 4;
+                table.extend_from_slice(&(sector_offset as u32).to_le_bytes());
+                for sector in &sectors {
+                    sector_offset += sector.len();
+                    table.extend_from_slice(&(sector_offset as u32).to_le_bytes());
+                }
+                if block.is_encrypted() {
+                    crypto::encrypt(&mut table, key.wrapping_sub(1));
+                }
+                packed.extend(table);
+            }
+            for (i, sector) in sectors.iter_mut().enumerate() {
+                if block.is_encrypted() {
+                    crypto::encrypt(sector, key.wrapping_add(i as u32));
+                }
+                packed.extend_from_slice(sector);
+            }
+
+            block.packed_size = packed.len() as u32;
+            archive.extend(packed);
+            for value in &[
+                block.offset as u32,
+                block.packed_size,
+                block.size,
+                block.flags,
+            ] {
+                block_table.extend_from_slice(&value.to_le_bytes());
+            }
+
+            let mut slot = crypto::hash_string(file.name, HashType::TableOffset) as usize;
+            while hash_table[(slot % hash_table_entries) * TABLE_ENTRY_SIZE + 12] != 0xFF {
+                slot += 1;
+            }
+            let entry = &mut hash_table[(slot % hash_table_entries) * TABLE_ENTRY_SIZE..];
+            LittleEndian::write_u32(
+                &mut entry[0..],
+                crypto::hash_string(file.name, HashType::NameA),
+            );
+            LittleEndian::write_u32(
+                &mut entry[4..],
+                crypto::hash_string(file.name, HashType::NameB),
+            );
+            LittleEndian::write_u32(&mut entry[8..], 0);
+            LittleEndian::write_u32(&mut entry[12..], block_index as u32);
+        }
+
+        let hash_table_offset = archive.len();
+        crypto::encrypt(
+            &mut hash_table,
+            crypto::hash_string(HASH_TABLE_KEY, HashType::FileKey),
+        );
+        archive.extend(hash_table);
+        let block_table_offset = archive.len();
+        crypto::encrypt(
+            &mut block_table,
+            crypto::hash_string(BLOCK_TABLE_KEY, HashType::FileKey),
+        );
+        archive.extend(block_table);
+
+        let header = &mut archive[..HEADER_SIZE_V2 as usize];
+        LittleEndian::write_u32(&mut header[0..], HEADER_SIGNATURE);
+        LittleEndian::write_u32(&mut header[4..], HEADER_SIZE_V1 as u32);
+        LittleEndian::write_u32(&mut header[16..], hash_table_offset as u32);
+        LittleEndian::write_u32(&mut header[20..], block_table_offset as u32);
+        LittleEndian::write_u32(&mut header[24..], hash_table_entries as u32);
+        LittleEndian::write_u32(&mut header[28..], files.len() as u32);
+
+        let mut buf = vec![0u8; archive_offset];
+        buf.extend(archive);
+        buf
+    }
+
+    /// Turns an archive from `build_archive` into the v2 format, appending `hi_offsets` as
+    /// its hi-block table
+    fn upgrade_to_v2(buf: &mut Vec<u8>, hi_offsets: &[u16]) {
+        let header_offset = HEADER_ALIGNMENT as usize;
+        let hi_block_table_offset = buf.len() - header_offset;
+        for hi in hi_offsets {
+            buf.extend_from_slice(&hi.to_le_bytes());
+        }
+
+        let header = &mut buf[header_offset..header_offset + HEADER_SIZE_V2 as usize];
+        LittleEndian::write_u32(&mut header[4..], HEADER_SIZE_V2);
+        LittleEndian::write_u16(&mut header[12..], FORMAT_VERSION_2);
+        LittleEndian::write_u64(&mut header[32..], hi_block_table_offset as u64);
+    }
+
+    #[test]
+    fn test_mpq_archive_reads_plain_compressed_and_encrypted_files() {
+        let units = (0..1200u32).map(|i| (i % 7) as u8).collect::<Vec<_>>();
+        let buf = build_archive(&[
+            TestFile {
+                name: "(listfile)",
+                data: b"staredit\\scenario.chk\r\narr\\units.dat".to_vec(),
+                flags: 0,
+            },
+            TestFile {
+                name: "staredit\\scenario.chk",
+                data: b"VER \x02\x00\x00\x00\xCD\x00".to_vec(),
+                flags: Block::COMPRESS,
+            },
+            TestFile {
+                name: "arr\\units.dat",
+                data: units.clone(),
+                flags: Block::COMPRESS | Block::ENCRYPTED | Block::FIX_KEY,
+            },
+        ]);
+        let archive = MPQArchive::from_reader("test.mpq", Cursor::new(buf)).unwrap();
+
+        assert_eq!(archive.header().offset, HEADER_ALIGNMENT);
+        assert_eq!(archive.read_file("ARR/UNITS.DAT").unwrap(), units);
+        assert_eq!(
+            archive.read_file("staredit\\scenario.chk").unwrap(),
+            b"VER \x02\x00\x00\x00\xCD\x00".to_vec()
+        );
+        assert_eq!(
+            ReadOnlyMPQArchive::files(&archive).unwrap(),
+            vec!["arr\\units.dat", "staredit\\scenario.chk"]
+        );
+        assert_eq!(
+            ReadOnlyMPQArchive::size(&archive, "arr\\units.dat").unwrap(),
+            1200
+        );
+        assert!(archive
+            .read_file("arr\\flingy.dat")
+            .unwrap_err()
+            .is_not_found());
+    }
+
+    #[test]
+    fn test_mpq_archive_reads_unlisted_and_unnamed_files() {
+        let scenario = (0..700u32).map(|i| (i % 5) as u8).collect::<Vec<_>>();
+        let buf = build_archive(&[
+            TestFile {
+                name: "(listfile)",
+                data: b"arr\\units.dat".to_vec(),
+                flags: 0,
+            },
+            TestFile {
+                name: "arr\\units.dat",
+                data: b"units".to_vec(),
+                flags: 0,
+            },
+            TestFile {
+                name: "staredit\\scenario.chk",
+                data: scenario.clone(),
+                flags: Block::COMPRESS | Block::ENCRYPTED | Block::FIX_KEY,
+            },
+        ]);
+        let archive = MPQArchive::from_reader("protected.scx", Cursor::new(buf)).unwrap();
+
+        const SCENARIO: FileHash = FileHash::new("staredit\\scenario.chk");
+        assert_eq!(archive.read_file_by_hash(&SCENARIO).unwrap(), scenario);
+        assert_eq!(archive.unnamed_blocks(vec![]), vec![2]);
+        assert!(archive
+            .unnamed_blocks(vec!["STAREDIT\\SCENARIO.CHK"])
+            .is_empty());
+        assert_eq!(archive.recover_block(2).unwrap(), scenario);
+        assert!(archive.recover_block(3).unwrap_err().is_not_found());
+    }
+
+    #[test]
+    fn test_mpq_archive_reads_v2_header() {
+        let files = [
+            TestFile {
+                name: "arr\\units.dat",
+                data: b"units".to_vec(),
+                flags: 0,
+            },
+            TestFile {
+                name: "arr\\flingy.dat",
+                data: b"flingy".to_vec(),
+                flags: Block::COMPRESS,
+            },
+        ];
+
+        let mut buf = build_archive(&files);
+        let hi_block_table_offset = buf.len() as u64;
+        upgrade_to_v2(&mut buf, &[0, 0]);
+        let archive = MPQArchive::from_reader("v2.mpq", Cursor::new(buf)).unwrap();
+        assert_eq!(archive.header().format_version, FORMAT_VERSION_2);
+        assert_eq!(
+            archive.header().hi_block_table_offset,
+            Some(hi_block_table_offset)
+        );
+        assert_eq!(archive.read_file("arr\\units.dat").unwrap(), b"units");
+        assert_eq!(archive.read_file("arr\\flingy.dat").unwrap(), b"flingy");
+
+        // the hi-block table moves the second file past 4 GiB
+        let mut buf = build_archive(&files);
+        upgrade_to_v2(&mut buf, &[0, 1]);
+        let archive = MPQArchive::from_reader("v2.mpq", Cursor::new(buf)).unwrap();
+        assert_eq!(archive.read_file("arr\\units.dat").unwrap(), b"units");
+        assert!(archive.read_file("arr\\flingy.dat").is_err());
+
+        // protected maps set the v2 version with a v1 header size and garbage after it
+        let mut buf = build_archive(&files);
+        upgrade_to_v2(&mut buf, &[0, 1]);
+        let header_offset = HEADER_ALIGNMENT as usize;
+        LittleEndian::write_u32(&mut buf[header_offset + 4..], HEADER_SIZE_V1 as u32);
+        LittleEndian::write_u32(&mut buf[header_offset + 40..], 0xFFFF_FFFF);
+        let archive = MPQArchive::from_reader("protected.scx", Cursor::new(buf)).unwrap();
+        assert_eq!(archive.header().hi_block_table_offset, None);
+        assert_eq!(archive.read_file("arr\\flingy.dat").unwrap(), b"flingy");
+    }
+
+    #[test]
+    fn test_mapped_archive_borrows_sectors_from_the_map() {
+        let units = (0..1200u32).map(|i| (i % 7) as u8).collect::<Vec<_>>();
+        let buf = build_archive(&[
+            TestFile {
+                name: "arr\\units.dat",
+                data: units.clone(),
+                flags: 0,
+            },
+            TestFile {
+                name: "arr\\flingy.dat",
+                data: units.clone(),
+                flags: Block::COMPRESS | Block::ENCRYPTED | Block::FIX_KEY,
+            },
+        ]);
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&buf).unwrap();
+        let archive = MPQArchive::map(file.path()).unwrap();
+
+        assert_eq!(archive.read_file("arr\\units.dat").unwrap(), units);
+        assert_eq!(archive.read_file("arr\\flingy.dat").unwrap(), units);
+        assert!(matches!(
+            archive.read_at(HEADER_ALIGNMENT, 4).unwrap(),
+            Cow::Borrowed(b"MPQ\x1A")
+        ));
+        assert!(archive.read_at(buf.len() as u64 - 2, 4).is_err());
+
+        let mut reader = archive.open_file("arr\\units.dat").unwrap();
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, units);
+    }
+
+    #[test]
+    fn test_mpq_archive_decodes_huffman_and_adpcm_sounds_into_a_wav() {
+        use super::super::{adpcm, huffman};
+        use crate::wav::Wav;
+
+        for channels in 1..=2u16 {
+            // Storm packs the header sector as usual and every later sector as ADPCM,
+            // then Huffman codes the ADPCM stream with type 6 to 8. Those tables are not
+            // included yet, so this codes it with type 0 to cover the rest of the path.
+            let mut stream = vec![0, 4];
+            for channel in 0..channels {
+                stream.extend_from_slice(&(channel as i16 * -1000 + 500).to_le_bytes());
+            }
+            let encoded = [0x05, 0x45, 0x12, 0x80];
+            stream.extend((0..0x100 - channels as usize).map(|i| encoded[i % encoded.len()]));
+            let pcm = adpcm::decompress(&stream, channels as usize, 0x200).unwrap();
+            assert_eq!(pcm.len(), 0x200);
+
+            let mut wav = Wav {
+                sample_rate: 22050,
+                channels,
+                samples: vec![0; (0x200 - 44) / 2],
+            };
+            wav.samples
+                .extend(pcm.chunks_exact(2).map(LittleEndian::read_i16));
+            let mut data = Vec::new();
+            wav.write(&mut data).unwrap();
+            assert_eq!(data.len(), 0x400);
+
+            let mask = if channels == 1 { 0x41 } else { 0x81 };
+            let buf = build_archive_with(
+                &[TestFile {
+                    name: "sound\\zerg\\drone\\zdrpss00.wav",
+                    data,
+                    flags: Block::COMPRESS | Block::ENCRYPTED | Block::FIX_KEY,
+                }],
+                |index, sector| {
+                    if index == 0 {
+                        return zlib_sector(sector);
+                    }
+                    let mut packed = vec![mask];
+                    packed.extend(huffman::compress(&stream));
+                    packed
+                },
+            );
+            let archive = MPQArchive::from_reader("sound.mpq", Cursor::new(buf)).unwrap();
+
+            let file = archive
+                .read_file("sound\\zerg\\drone\\zdrpss00.wav")
+                .unwrap();
+            assert_eq!(Wav::from_buffer(&mut Cursor::new(&file)).unwrap(), wav);
+        }
+    }
+
+    #[test]
+    fn test_mpq_archive_probes_truncated_hash_tables_from_the_declared_size() {
+        const SCENARIO: FileHash = FileHash::new("staredit\\scenario.chk");
+        let buf = build_archive(&[TestFile {
+            name: "staredit\\scenario.chk",
+            data: b"VER ".to_vec(),
+            flags: 0,
+        }]);
+        let mut archive = MPQArchive::from_reader("protected.scx", Cursor::new(buf)).unwrap();
+
+        // as if the map declared 8 entries but only those up to the scenario's slot fit
+        let slot = SCENARIO.table_offset as usize & 7;
+        assert!(slot >= 2);
+        archive.hash_table.truncate(slot + 1);
+        assert_eq!(archive.read_file_by_hash(&SCENARIO).unwrap(), b"VER ");
+
+        archive.hash_table.truncate(slot);
+        assert!(archive
+            .read_file_by_hash(&SCENARIO)
+            .unwrap_err()
+            .is_not_found());
+    }
+}
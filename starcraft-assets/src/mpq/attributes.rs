@@ -0,0 +1,239 @@
+use super::super::errors::*;
+use super::{ArchiveSource, MPQArchive};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{Cursor, Read};
+
+pub const ATTRIBUTES_NAME: &str = "(attributes)";
+const VERSION: u32 = 100;
+
+/// The checksums and timestamps an archive keeps for each of its blocks.
+#[derive(Debug, Clone, Default)]
+pub struct Attributes {
+    pub crc32s: Option<Vec<u32>>,
+    /// Windows `FILETIME`s
+    pub timestamps: Option<Vec<u64>>,
+    pub md5s: Option<Vec<[u8; 16]>>,
+}
+
+impl Attributes {
+    const CRC32: u32 = 0x1;
+    const TIMESTAMP: u32 = 0x2;
+    const MD5: u32 = 0x4;
+
+    /// Parses an `(attributes)` file of an archive with `block_count` blocks
+    pub fn from_buffer(
+        cursor: &mut Cursor<&Vec<u8>>,
+        block_count: usize,
+        archive: &str,
+    ) -> Result<Attributes> {
+        (|| -> std::io::Result<Attributes> {
+            let version = cursor.read_u32::<LittleEndian>()?;
+            if version != VERSION {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("unsupported attributes version {}", version),
+                ));
+            }
+
+            let flags = cursor.read_u32::<LittleEndian>()?;
+            let mut attributes = Attributes::default();
+            if flags & Attributes::CRC32 != 0 {
+                attributes.crc32s = Some(
+                    (0..block_count)
+                        .map(|_| cursor.read_u32::<LittleEndian>())
+                        .collect::<std::io::Result<_>>()?,
+                );
+            }
+            if flags & Attributes::TIMESTAMP != 0 {
+                attributes.timestamps = Some(
+                    (0..block_count)
+                        .map(|_| cursor.read_u64::<LittleEndian>())
+                        .collect::<std::io::Result<_>>()?,
+                );
+            }
+            if flags & Attributes::MD5 != 0 {
+                attributes.md5s = Some(
+                    (0..block_count)
+                        .map(|_| {
+                            let mut md5 = [0u8; 16];
+                            cursor.read_exact(&mut md5).map(|_| md5)
+                        })
+                        .collect::<std::io::Result<_>>()?,
+                );
+            }
+
+            Ok(attributes)
+        })()
+        .chain_err(|| {
+            ErrorKind::Corrupt(
+                ATTRIBUTES_NAME.into(),
+                archive.into(),
+                Some(cursor.position()),
+            )
+        })
+    }
+
+    /// The recorded CRC32 of block `index`, unless it was left out
+    pub fn crc32(&self, index: usize) -> Option<u32> {
+        self.crc32s
+            .as_ref()
+            .and_then(|crc32s| crc32s.get(index).copied())
+            .filter(|crc32| *crc32 != 0)
+    }
+
+    /// The recorded MD5 of block `index`, unless it was left out
+    pub fn md5(&self, index: usize) -> Option<[u8; 16]> {
+        self.md5s
+            .as_ref()
+            .and_then(|md5s| md5s.get(index).copied())
+            .filter(|md5| *md5 != [0u8; 16])
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum MismatchKind {
+    Crc32 {
+        expected: u32,
+        actual: u32,
+    },
+    Md5 {
+        expected: [u8; 16],
+        actual: [u8; 16],
+    },
+    /// The file could not be read to be checked
+    Unreadable(String),
+}
+
+/// A block whose contents do not match its attributes.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Mismatch {
+    pub block: usize,
+    /// The name of the file, if it is known
+    pub file_name: Option<String>,
+    pub kind: MismatchKind,
+}
+
+impl<S> MPQArchive<S>
+where
+    S: ArchiveSource,
+{
+    pub fn attributes(&self) -> Result<Attributes> {
+        let buf = self.read_file(ATTRIBUTES_NAME)?;
+        Attributes::from_buffer(&mut Cursor::new(&buf), self.blocks().len(), self.name())
+    }
+
+    /// Checks every file against the CRC32 and MD5 recorded in the archive's `(attributes)`.
+    ///
+    /// Files missing from the listfile are checked too, as long as their blocks can be
+    /// recovered. Fails if the archive has no readable `(attributes)`.
+    pub fn verify(&self) -> Result<Vec<Mismatch>> {
+        let attributes = self.attributes()?;
+        let names = self.block_names(Vec::new());
+
+        let mut mismatches = Vec::new();
+        for (index, block) in self.blocks().iter().enumerate() {
+            let file_name = names[index].clone();
+            let (expected_crc32, expected_md5) = (attributes.crc32(index), attributes.md5(index));
+            if !block.exists()
+                || file_name.as_deref() == Some(ATTRIBUTES_NAME)
+                || (expected_crc32.is_none() && expected_md5.is_none())
+            {
+                continue;
+            }
+
+            let data = match &file_name {
+                Some(file_name) => self.read_file(file_name),
+                None => self.recover_block(index),
+            };
+            let mut mismatch = |kind| {
+                mismatches.push(Mismatch {
+                    block: index,
+                    file_name: file_name.clone(),
+                    kind,
+                })
+            };
+
+            let data = match data {
+                Ok(data) => data,
+                Err(err) => {
+                    mismatch(MismatchKind::Unreadable(err.to_string()));
+                    continue;
+                }
+            };
+            if let Some(expected) = expected_crc32 {
+                let actual = crc32fast::hash(&data);
+                if actual != expected {
+                    mismatch(MismatchKind::Crc32 { expected, actual });
+                }
+            }
+            if let Some(expected) = expected_md5 {
+                let actual = md5::compute(&data).0;
+                if actual != expected {
+                    mismatch(MismatchKind::Md5 { expected, actual });
+                }
+            }
+        }
+
+        Ok(mismatches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::archive::tests::{build_archive, TestFile};
+    use super::*;
+
+    fn attributes(files: &[&[u8]]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&VERSION.to_le_bytes());
+        buf.extend_from_slice(&(Attributes::CRC32 | Attributes::MD5).to_le_bytes());
+        for data in files {
+            buf.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+        }
+        for data in files {
+            buf.extend_from_slice(&md5::compute(data).0);
+        }
+
+        buf
+    }
+
+    #[test]
+    fn test_verify_reports_files_that_do_not_match_their_attributes() {
+        let listfile = b"arr\\units.dat\r\narr\\flingy.dat".to_vec();
+        let buf = build_archive(&[
+            TestFile {
+                name: "(listfile)",
+                data: listfile.clone(),
+                flags: 0,
+            },
+            TestFile {
+                name: "arr\\units.dat",
+                data: b"units".to_vec(),
+                flags: 0,
+            },
+            TestFile {
+                name: "arr\\flingy.dat",
+                data: b"flingy, corrupted".to_vec(),
+                flags: 0,
+            },
+            TestFile {
+                name: ATTRIBUTES_NAME,
+                data: attributes(&[&listfile, b"units", b"flingy", &[]]),
+                flags: 0,
+            },
+        ]);
+        let archive = MPQArchive::from_reader("StarDat.mpq", Cursor::new(buf)).unwrap();
+
+        let mismatches = archive.verify().unwrap();
+        assert_eq!(mismatches.len(), 2);
+        assert_eq!(mismatches[0].file_name.as_deref(), Some("arr\\flingy.dat"));
+        assert_eq!(
+            mismatches[0].kind,
+            MismatchKind::Crc32 {
+                expected: crc32fast::hash(b"flingy"),
+                actual: crc32fast::hash(b"flingy, corrupted"),
+            }
+        );
+        assert!(matches!(mismatches[1].kind, MismatchKind::Md5 { .. }));
+    }
+}
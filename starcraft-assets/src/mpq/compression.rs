@@ -0,0 +1,176 @@
+use super::{adpcm, explode, huffman};
+use std::io::{self, Read};
+
+const HUFFMAN: u8 = 0x01;
+const ZLIB: u8 = 0x02;
+const IMPLODE: u8 = 0x08;
+const BZIP2: u8 = 0x10;
+const ADPCM_MONO: u8 = 0x40;
+const ADPCM_STEREO: u8 = 0x80;
+
+/// Decompresses a sector of a file with the `COMPRESS` flag into at most `size` bytes.
+///
+/// The first byte of the sector lists the compressions that were applied, which are
+/// undone in the reverse order.
+pub fn decompress(buf: &[u8], size: usize) -> io::Result<Vec<u8>> {
+    let (&mask, data) = buf
+        .split_first()
+        .ok_or_else(|| invalid_data("empty compressed sector"))?;
+    if mask & !(HUFFMAN | ZLIB | IMPLODE | BZIP2 | ADPCM_MONO | ADPCM_STEREO) != 0 {
+        return Err(invalid_data(format!(
+            "unsupported compression 0x{:02X}",
+            mask
+        )));
+    }
+
+    let mut data = data.to_vec();
+    if mask & BZIP2 != 0 {
+        data = read_limited(bzip2_rs::DecoderReader::new(data.as_slice()), size)?;
+    }
+    if mask & IMPLODE != 0 {
+        data = explode::explode(&data, size)?;
+    }
+    if mask & ZLIB != 0 {
+        data = read_limited(flate2::read::ZlibDecoder::new(data.as_slice()), size)?;
+    }
+    if mask & HUFFMAN != 0 {
+        data = huffman::decompress(&data, size)?;
+    }
+    if mask & ADPCM_STEREO != 0 {
+        data = adpcm::decompress(&data, 2, size)?;
+    }
+    if mask & ADPCM_MONO != 0 {
+        data = adpcm::decompress(&data, 1, size)?;
+    }
+
+    Ok(data)
+}
+
+fn read_limited(reader: impl Read, size: usize) -> io::Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(size);
+    reader.take(size as u64).read_to_end(&mut data)?;
+
+    Ok(data)
+}
+
+pub(super) fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Reads bits least significant first, as both the implode and Huffman coders write them.
+pub(super) struct BitReader<'a> {
+    input: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(input: &'a [u8]) -> BitReader<'a> {
+        BitReader {
+            input,
+            position: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    /// Reads up to 24 bits
+    pub fn bits(&mut self, count: u32) -> io::Result<u32> {
+        while self.count < count {
+            let byte = self
+                .input
+                .get(self.position)
+                .ok_or_else(|| invalid_data("compressed stream ended early"))?;
+            self.buffer |= (*byte as u32) << self.count;
+            self.position += 1;
+            self.count += 8;
+        }
+
+        let value = self.buffer & ((1 << count) - 1);
+        self.buffer >>= count;
+        self.count -= count;
+
+        Ok(value)
+    }
+
+    pub fn bit(&mut self) -> io::Result<bool> {
+        self.bits(1).map(|bit| bit == 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(data: &[u8]) -> Vec<i16> {
+        data.chunks_exact(2)
+            .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+            .collect()
+    }
+
+    const ADPCM_MONO_SECTOR: [u8; 7] = [0x00, 0x04, 0x64, 0x00, 0x05, 0x80, 0x41];
+    const ADPCM_STEREO_SECTOR: [u8; 11] = [
+        0x00, 0x04, 0xE8, 0x03, 0x18, 0xFC, 0x01, 0x41, 0x81, 0x02, 0x00,
+    ];
+
+    #[test]
+    fn test_decompress_bzip2() {
+        let sector = [
+            0x10, 0x42, 0x5A, 0x68, 0x39, 0x31, 0x41, 0x59, 0x26, 0x53, 0x59, 0xDE, 0x89, 0xAB,
+            0x88, 0x00, 0x00, 0x04, 0x13, 0x80, 0x40, 0x01, 0x00, 0x04, 0x24, 0x21, 0x1E, 0x00,
+            0x20, 0x00, 0x22, 0x3D, 0x50, 0x1A, 0x7E, 0x94, 0x20, 0x1A, 0x69, 0xA1, 0xA4, 0x4C,
+            0xEA, 0xAC, 0xE0, 0x90, 0xE8, 0x96, 0x60, 0x84, 0x3E, 0x2E, 0xE4, 0x8A, 0x70, 0xA1,
+            0x21, 0xBD, 0x13, 0x57, 0x10,
+        ];
+        let data = b"arr\\units.dat arr\\units.dat arr\\units.dat";
+        assert_eq!(decompress(&sector, data.len()).unwrap(), data.to_vec());
+        assert_eq!(decompress(&sector, 3).unwrap(), b"arr".to_vec());
+    }
+
+    #[test]
+    fn test_decompress_adpcm_mono() {
+        let mut sector = vec![ADPCM_MONO];
+        sector.extend_from_slice(&ADPCM_MONO_SECTOR);
+        // 100, +647 at step 494, repeated, then -578 at step 544
+        assert_eq!(
+            samples(&decompress(&sector, 64).unwrap()),
+            vec![100, 747, 747, 169]
+        );
+        assert_eq!(samples(&decompress(&sector, 5).unwrap()), vec![100, 747]);
+    }
+
+    #[test]
+    fn test_decompress_adpcm_stereo() {
+        let mut sector = vec![ADPCM_STEREO];
+        sector.extend_from_slice(&ADPCM_STEREO_SECTOR);
+        // 0x81 raises the left step to 1060 and keeps the next sample on the left channel
+        assert_eq!(
+            samples(&decompress(&sector, 64).unwrap()),
+            vec![1000, -1000, 1524, -1524, 2120, -1494]
+        );
+    }
+
+    #[test]
+    fn test_decompress_huffman_then_adpcm() {
+        let mut sector = vec![HUFFMAN | ADPCM_MONO];
+        sector.extend(huffman::compress(&ADPCM_MONO_SECTOR));
+        assert_eq!(
+            samples(&decompress(&sector, 64).unwrap()),
+            vec![100, 747, 747, 169]
+        );
+
+        let mut sector = vec![HUFFMAN | ADPCM_STEREO];
+        sector.extend(huffman::compress(&ADPCM_STEREO_SECTOR));
+        assert_eq!(
+            samples(&decompress(&sector, 64).unwrap()),
+            vec![1000, -1000, 1524, -1524, 2120, -1494]
+        );
+    }
+
+    #[test]
+    fn test_decompress_unsupported_mask() {
+        assert!(decompress(&[0x04, 0x00], 4).is_err());
+        assert!(decompress(&[], 4).is_err());
+    }
+}
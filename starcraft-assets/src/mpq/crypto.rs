@@ -0,0 +1,165 @@
+use byteorder::{ByteOrder, LittleEndian};
+
+/// What a name is hashed for; each uses a different part of the encryption table
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum HashType {
+    /// Starting slot in the hash table
+    TableOffset = 0,
+    NameA = 1,
+    NameB = 2,
+    /// Encryption key of a file or table
+    FileKey = 3,
+}
+
+const fn build_encryption_table() -> [u32; 0x500] {
+    let mut table = [0u32; 0x500];
+    let mut seed: u32 = 0x0010_0001;
+
+    let mut i = 0;
+    while i < 0x100 {
+        let mut index = i;
+        let mut j = 0;
+        while j < 5 {
+            seed = (seed * 125 + 3) % 0x002A_AAAB;
+            let high = (seed & 0xFFFF) << 16;
+            seed = (seed * 125 + 3) % 0x002A_AAAB;
+            let low = seed & 0xFFFF;
+
+            table[index] = high | low;
+            index += 0x100;
+            j += 1;
+        }
+        i += 1;
+    }
+
+    table
+}
+
+const ENCRYPTION_TABLE: &[u32; 0x500] = &build_encryption_table();
+
+/// Storm's hash of a file name, ignoring ASCII case and treating `/` like `\`
+pub const fn hash_string(name: &str, hash_type: HashType) -> u32 {
+    hash_bytes(name.as_bytes(), 0, hash_type)
+}
+
+/// The encryption key of a file before any position adjustment, which only depends on the
+/// name of the file without its folders
+pub const fn file_key(file_name: &str) -> u32 {
+    let name = file_name.as_bytes();
+    let mut start = name.len();
+    while start > 0 && name[start - 1] != b'\\' && name[start - 1] != b'/' {
+        start -= 1;
+    }
+
+    hash_bytes(name, start, HashType::FileKey)
+}
+
+const fn hash_bytes(name: &[u8], start: usize, hash_type: HashType) -> u32 {
+    let mut seed1: u32 = 0x7FED_7FED;
+    let mut seed2: u32 = 0xEEEE_EEEE;
+
+    let mut i = start;
+    while i < name.len() {
+        let c = match name[i] {
+            b'/' => b'\\',
+            c => c.to_ascii_uppercase(),
+        } as u32;
+
+        seed1 =
+            ENCRYPTION_TABLE[(hash_type as u32 * 0x100 + c) as usize] ^ seed1.wrapping_add(seed2);
+        seed2 = c
+            .wrapping_add(seed1)
+            .wrapping_add(seed2)
+            .wrapping_add(seed2 << 5)
+            .wrapping_add(3);
+        i += 1;
+    }
+
+    seed1
+}
+
+/// Recovers the key that encrypted `buf` from the known value of its first word and an
+/// upper bound of its second, by trying each table entry the first word could have used
+pub fn detect_key(buf: &[u8], first: u32, max_second: u32) -> Option<u32> {
+    if buf.len() < 8 {
+        return None;
+    }
+
+    let key_plus_seed = (LittleEndian::read_u32(buf) ^ first).wrapping_sub(0xEEEE_EEEE);
+    ENCRYPTION_TABLE[0x400..]
+        .iter()
+        .map(|seed| key_plus_seed.wrapping_sub(*seed))
+        .find(|key| {
+            let mut words = [0u8; 8];
+            words.copy_from_slice(&buf[..8]);
+            decrypt(&mut words, *key);
+
+            LittleEndian::read_u32(&words) == first
+                && (first..=max_second).contains(&LittleEndian::read_u32(&words[4..]))
+        })
+}
+
+/// Decrypts `buf` in place. A trailing partial word is left as is.
+pub fn decrypt(buf: &mut [u8], mut key: u32) {
+    let mut seed: u32 = 0xEEEE_EEEE;
+    for word in buf.chunks_exact_mut(4) {
+        seed = seed.wrapping_add(ENCRYPTION_TABLE[0x400 + (key & 0xFF) as usize]);
+        let value = LittleEndian::read_u32(word) ^ key.wrapping_add(seed);
+
+        key = (!key << 0x15).wrapping_add(0x1111_1111) | (key >> 0x0B);
+        seed = value
+            .wrapping_add(seed)
+            .wrapping_add(seed << 5)
+            .wrapping_add(3);
+        LittleEndian::write_u32(word, value);
+    }
+}
+
+/// Encrypts `buf` in place, the inverse of `decrypt`
+pub fn encrypt(buf: &mut [u8], mut key: u32) {
+    let mut seed: u32 = 0xEEEE_EEEE;
+    for word in buf.chunks_exact_mut(4) {
+        seed = seed.wrapping_add(ENCRYPTION_TABLE[0x400 + (key & 0xFF) as usize]);
+        let value = LittleEndian::read_u32(word);
+
+        LittleEndian::write_u32(word, value ^ key.wrapping_add(seed));
+        key = (!key << 0x15).wrapping_add(0x1111_1111) | (key >> 0x0B);
+        seed = value
+            .wrapping_add(seed)
+            .wrapping_add(seed << 5)
+            .wrapping_add(3);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_keys_match_storm() {
+        assert_eq!(hash_string("(hash table)", HashType::FileKey), 0xC3AF_3770);
+        assert_eq!(hash_string("(block table)", HashType::FileKey), 0xEC83_B3A3);
+        assert_eq!(
+            hash_string("unit/zerg/zergling.grp", HashType::NameA),
+            hash_string("UNIT\\ZERG\\ZERGLING.GRP", HashType::NameA)
+        );
+
+        let mut buf = b"StarCraft!".to_vec();
+        encrypt(&mut buf, 0x1234_5678);
+        assert_ne!(&buf[..8], b"StarCraf");
+        decrypt(&mut buf, 0x1234_5678);
+        assert_eq!(buf, b"StarCraft!".to_vec());
+
+        // a sector offset table of two sectors
+        let mut sector_offsets = [12u8, 0, 0, 0, 0x10, 0x02, 0, 0, 0x80, 0x03, 0, 0];
+        encrypt(&mut sector_offsets, 0x1234_5678);
+        assert_eq!(
+            detect_key(&sector_offsets, 12, 12 + 0x1000),
+            Some(0x1234_5678)
+        );
+        assert_eq!(
+            file_key("unit\\zerg\\zergling.grp"),
+            hash_string("zergling.grp", HashType::FileKey)
+        );
+    }
+}
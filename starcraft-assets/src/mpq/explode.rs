@@ -0,0 +1,145 @@
+use super::compression::{invalid_data, BitReader};
+use std::io;
+
+const MAX_CODE_BITS: usize = 13;
+
+// Code lengths of the fixed Huffman codes, run length encoded: every byte holds
+// `(repeat - 1) << 4 | length`.
+const LITERAL_CODE_LENGTHS: [u8; 98] = [
+    11, 124, 8, 7, 28, 7, 188, 13, 76, 4, 10, 8, 12, 10, 12, 10, 8, 23, 8, 9, 7, 6, 7, 8, 7, 6, 55,
+    8, 23, 24, 12, 11, 7, 9, 11, 12, 6, 7, 22, 5, 7, 24, 6, 11, 9, 6, 7, 22, 7, 11, 38, 7, 9, 8,
+    25, 11, 8, 11, 9, 12, 8, 12, 5, 38, 5, 38, 5, 11, 7, 5, 6, 21, 6, 10, 53, 8, 7, 24, 10, 27, 44,
+    253, 253, 253, 252, 252, 252, 13, 12, 45, 12, 45, 12, 61, 12, 45, 44, 173,
+];
+const LENGTH_CODE_LENGTHS: [u8; 6] = [2, 35, 36, 53, 38, 23];
+const DISTANCE_CODE_LENGTHS: [u8; 7] = [2, 20, 53, 230, 247, 151, 248];
+
+const LENGTH_BASE: [u16; 16] = [3, 2, 4, 5, 6, 7, 8, 9, 10, 12, 16, 24, 40, 72, 136, 264];
+const LENGTH_EXTRA_BITS: [u8; 16] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8];
+const END_OF_STREAM: usize = 519;
+
+/// A canonical Huffman code, decoded one bit at a time.
+struct Code {
+    counts: [u16; MAX_CODE_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Code {
+    fn new(compact_lengths: &[u8]) -> Code {
+        let lengths = compact_lengths
+            .iter()
+            .flat_map(|byte| std::iter::repeat_n((byte & 0xF) as usize, (byte >> 4) as usize + 1))
+            .collect::<Vec<_>>();
+
+        let mut counts = [0u16; MAX_CODE_BITS + 1];
+        for length in &lengths {
+            counts[*length] += 1;
+        }
+
+        let mut offsets = [0usize; MAX_CODE_BITS + 1];
+        let mut offset = 0;
+        for (length, count) in counts.iter().enumerate().skip(1) {
+            offsets[length] = offset;
+            offset += *count as usize;
+        }
+
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, length) in lengths.iter().enumerate() {
+            if *length != 0 {
+                symbols[offsets[*length]] = symbol as u16;
+                offsets[*length] += 1;
+            }
+        }
+
+        Code { counts, symbols }
+    }
+
+    /// Codes are stored with their bits inverted
+    fn decode(&self, bits: &mut BitReader) -> io::Result<usize> {
+        let (mut code, mut first, mut index) = (0usize, 0usize, 0usize);
+        for count in self.counts.iter().skip(1) {
+            code |= !bits.bit()? as usize;
+            let count = *count as usize;
+            if code < first + count {
+                return Ok(self.symbols[index + code - first] as usize);
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(invalid_data("invalid implode code"))
+    }
+}
+
+/// Decompresses PKWARE Data Compression Library "implode" data into at most `size` bytes.
+pub fn explode(input: &[u8], size: usize) -> io::Result<Vec<u8>> {
+    let mut bits = BitReader::new(input);
+    let coded_literals = match bits.bits(8)? {
+        0 => false,
+        1 => true,
+        mode => {
+            return Err(invalid_data(format!(
+                "invalid implode literal mode {}",
+                mode
+            )))
+        }
+    };
+    let dictionary_bits = bits.bits(8)?;
+    if !(4..=6).contains(&dictionary_bits) {
+        return Err(invalid_data(format!(
+            "invalid implode dictionary size {}",
+            dictionary_bits
+        )));
+    }
+
+    let literals = Code::new(&LITERAL_CODE_LENGTHS);
+    let lengths = Code::new(&LENGTH_CODE_LENGTHS);
+    let distances = Code::new(&DISTANCE_CODE_LENGTHS);
+
+    let mut output = Vec::with_capacity(size);
+    while output.len() < size {
+        if bits.bit()? {
+            let symbol = lengths.decode(&mut bits)?;
+            let length = LENGTH_BASE[symbol] as usize
+                + bits.bits(LENGTH_EXTRA_BITS[symbol] as u32)? as usize;
+            if length == END_OF_STREAM {
+                break;
+            }
+
+            let extra_bits = if length == 2 { 2 } else { dictionary_bits };
+            let distance =
+                (distances.decode(&mut bits)? << extra_bits) + bits.bits(extra_bits)? as usize + 1;
+            if distance > output.len() {
+                return Err(invalid_data("implode distance too far back"));
+            }
+
+            let start = output.len() - distance;
+            for i in start..start + length {
+                let byte = output[i];
+                output.push(byte);
+            }
+        } else if coded_literals {
+            output.push(literals.decode(&mut bits)? as u8);
+        } else {
+            output.push(bits.bits(8)? as u8);
+        }
+    }
+    output.truncate(size);
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explode() {
+        let input = [0x00, 0x04, 0x82, 0x24, 0x25, 0x8f, 0x80, 0x7f];
+        assert_eq!(explode(&input, 64).unwrap(), b"AIAIAIAIAIAIA".to_vec());
+        assert_eq!(explode(&input, 4).unwrap(), b"AIAI".to_vec());
+        assert!(explode(&input[..4], 64).is_err());
+    }
+}
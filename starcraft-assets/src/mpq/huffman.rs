@@ -0,0 +1,359 @@
+use super::compression::{invalid_data, BitReader};
+use std::io;
+
+const END_OF_STREAM: u16 = 0x100;
+/// Followed by 8 raw bits of a byte that is not in the tree yet
+const NEW_BYTE: u16 = 0x101;
+const MAX_ITEMS: usize = 0x204;
+/// Sentinel of the item list, which is kept sorted by descending weight
+const HEAD: usize = 0;
+
+/// Initial weight of every byte for a compression type. Type 0 starts uniform and
+/// adapts to every decoded byte. Storm's fixed tables for types 1 to 8 are not included
+/// yet, so sectors using them are rejected instead of decoded with the wrong tree.
+fn byte_weights(compression_type: u32) -> Option<[u8; 256]> {
+    match compression_type {
+        0 => Some([0x0A; 256]),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Item {
+    prev: usize,
+    next: usize,
+    value: u16,
+    weight: u32,
+    parent: Option<usize>,
+    /// The lower weight child; the higher weight child is the item before it in the list
+    child_lo: Option<usize>,
+}
+
+/// Storm's adaptive Huffman tree.
+struct Tree {
+    items: Vec<Item>,
+    leaves: [Option<usize>; 0x102],
+}
+
+impl Tree {
+    fn new(weights: &[u8; 256]) -> Tree {
+        let mut tree = Tree {
+            items: Vec::with_capacity(MAX_ITEMS),
+            leaves: [None; 0x102],
+        };
+        tree.create(0, 0);
+
+        for (value, weight) in weights.iter().enumerate() {
+            if *weight != 0 {
+                let item = tree.create(value as u16, *weight as u32);
+                let at = tree.find_higher_or_equal(tree.last(), *weight as u32);
+                tree.insert_after(item, at);
+                tree.leaves[value] = Some(item);
+            }
+        }
+        for value in &[END_OF_STREAM, NEW_BYTE] {
+            let item = tree.create(*value, 1);
+            tree.insert_after(item, tree.last());
+            tree.leaves[*value as usize] = Some(item);
+        }
+
+        let mut child_lo = tree.last();
+        while child_lo != HEAD {
+            let child_hi = tree.items[child_lo].prev;
+            if child_hi == HEAD {
+                break;
+            }
+
+            let weight = tree.items[child_hi].weight + tree.items[child_lo].weight;
+            let parent = tree.create(0, weight);
+            tree.adopt(parent, child_lo, child_hi);
+            let at = tree.find_higher_or_equal(tree.items[child_hi].prev, weight);
+            tree.insert_after(parent, at);
+
+            child_lo = tree.items[child_hi].prev;
+        }
+
+        tree
+    }
+
+    fn first(&self) -> usize {
+        self.items[HEAD].next
+    }
+
+    fn last(&self) -> usize {
+        self.items[HEAD].prev
+    }
+
+    /// Adds an item that is not linked into the list yet
+    fn create(&mut self, value: u16, weight: u32) -> usize {
+        let index = self.items.len();
+        self.items.push(Item {
+            prev: index,
+            next: index,
+            value,
+            weight,
+            parent: None,
+            child_lo: None,
+        });
+
+        index
+    }
+
+    fn adopt(&mut self, parent: usize, child_lo: usize, child_hi: usize) {
+        self.items[parent].child_lo = Some(child_lo);
+        self.items[child_lo].parent = Some(parent);
+        self.items[child_hi].parent = Some(parent);
+    }
+
+    fn unlink(&mut self, item: usize) {
+        let Item { prev, next, .. } = self.items[item];
+        self.items[prev].next = next;
+        self.items[next].prev = prev;
+    }
+
+    fn insert_after(&mut self, item: usize, at: usize) {
+        self.unlink(item);
+        let next = self.items[at].next;
+        self.items[item].prev = at;
+        self.items[item].next = next;
+        self.items[next].prev = item;
+        self.items[at].next = item;
+    }
+
+    /// The nearest item at or before `item` with at least `weight`, or the list head
+    fn find_higher_or_equal(&self, mut item: usize, weight: u32) -> usize {
+        while item != HEAD && self.items[item].weight < weight {
+            item = self.items[item].prev;
+        }
+
+        item
+    }
+
+    fn decode(&self, bits: &mut BitReader) -> io::Result<u16> {
+        let mut item = self.first();
+        while let Some(child_lo) = self.items[item].child_lo {
+            item = if bits.bit()? {
+                self.items[child_lo].prev
+            } else {
+                child_lo
+            };
+        }
+
+        Ok(self.items[item].value)
+    }
+
+    /// Splits the lightest leaf to make room for `value`
+    fn insert_byte(&mut self, value: u16) -> io::Result<()> {
+        if self.items.len() + 2 > MAX_ITEMS {
+            return Err(invalid_data("huffman tree is full"));
+        }
+
+        let last = self.last();
+        let last_value = self.items[last].value;
+        let child_hi = self.create(last_value, self.items[last].weight);
+        self.insert_after(child_hi, self.last());
+        let child_lo = self.create(value, 0);
+        self.insert_after(child_lo, self.last());
+
+        self.adopt(last, child_lo, child_hi);
+        self.leaves[last_value as usize] = Some(child_hi);
+        self.leaves[value as usize] = Some(child_lo);
+        self.increment(child_lo);
+
+        Ok(())
+    }
+
+    /// Increments the weight of `item` and its ancestors, swapping items to keep the
+    /// list sorted
+    fn increment(&mut self, item: usize) {
+        let mut current = Some(item);
+        while let Some(item) = current {
+            self.items[item].weight += 1;
+            let weight = self.items[item].weight;
+
+            let higher = self.find_higher_or_equal(self.items[item].prev, weight);
+            let swap = self.items[higher].next;
+            if swap != item {
+                self.insert_after(swap, item);
+                self.insert_after(item, higher);
+
+                let item_parent = self.items[item].parent;
+                let swap_parent = self.items[swap].parent;
+                let swap_sibling = swap_parent.and_then(|parent| self.items[parent].child_lo);
+                if let Some(parent) = item_parent {
+                    if self.items[parent].child_lo == Some(item) {
+                        self.items[parent].child_lo = Some(swap);
+                    }
+                }
+                if let Some(parent) = swap_parent {
+                    if swap_sibling == Some(swap) {
+                        self.items[parent].child_lo = Some(item);
+                    }
+                }
+                self.items[item].parent = swap_parent;
+                self.items[swap].parent = item_parent;
+            }
+
+            current = self.items[item].parent;
+        }
+    }
+}
+
+/// Decompresses Storm's Huffman coding into at most `size` bytes.
+pub fn decompress(input: &[u8], size: usize) -> io::Result<Vec<u8>> {
+    if size == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut bits = BitReader::new(input);
+    let compression_type = bits.bits(8)?;
+    let weights = byte_weights(compression_type).ok_or_else(|| {
+        invalid_data(format!(
+            "unsupported huffman compression type {}",
+            compression_type
+        ))
+    })?;
+
+    decode(&mut bits, size, &weights, compression_type == 0)
+}
+
+/// Decodes values with a tree built from `weights` until the end of the stream or `size`
+/// bytes. An adaptive tree counts every decoded byte, a fixed one only the inserted ones.
+fn decode(
+    bits: &mut BitReader,
+    size: usize,
+    weights: &[u8; 256],
+    adaptive: bool,
+) -> io::Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(size);
+    let mut tree = Tree::new(weights);
+    loop {
+        let mut value = tree.decode(bits)?;
+        if value == END_OF_STREAM {
+            break;
+        }
+        let mut inserted = false;
+        if value == NEW_BYTE {
+            value = bits.bits(8)? as u16;
+            tree.insert_byte(value)?;
+            inserted = true;
+        }
+        if let (Some(leaf), true) = (tree.leaves[value as usize], inserted && !adaptive) {
+            tree.increment(leaf);
+        }
+
+        output.push(value as u8);
+        if output.len() >= size {
+            break;
+        }
+        if let (Some(leaf), true) = (tree.leaves[value as usize], adaptive) {
+            tree.increment(leaf);
+        }
+    }
+
+    Ok(output)
+}
+
+/// Compresses `data` with compression type 0, for building test sectors.
+#[cfg(test)]
+pub(super) fn compress(data: &[u8]) -> Vec<u8> {
+    let mut output = vec![0];
+    encode(&mut output, data, &byte_weights(0).unwrap(), true);
+
+    output
+}
+
+/// Mirrors `decode`, writing the path from the root to each value least significant bit
+/// first.
+#[cfg(test)]
+fn encode(output: &mut Vec<u8>, data: &[u8], weights: &[u8; 256], adaptive: bool) {
+    let mut bits = Vec::new();
+    let mut tree = Tree::new(weights);
+    let path = |tree: &Tree, bits: &mut Vec<bool>, value: u16| {
+        let mut item = tree.leaves[value as usize].unwrap();
+        let start = bits.len();
+        while let Some(parent) = tree.items[item].parent {
+            bits.push(tree.items[parent].child_lo != Some(item));
+            item = parent;
+        }
+        bits[start..].reverse();
+    };
+
+    for byte in data {
+        let value = *byte as u16;
+        if tree.leaves[value as usize].is_none() {
+            path(&tree, &mut bits, NEW_BYTE);
+            bits.extend((0..8).map(|bit| byte & (1 << bit) != 0));
+            tree.insert_byte(value).unwrap();
+            if !adaptive {
+                tree.increment(tree.leaves[value as usize].unwrap());
+            }
+        } else {
+            path(&tree, &mut bits, value);
+        }
+        if adaptive {
+            tree.increment(tree.leaves[value as usize].unwrap());
+        }
+    }
+    path(&tree, &mut bits, END_OF_STREAM);
+
+    for chunk in bits.chunks(8) {
+        let byte = chunk
+            .iter()
+            .enumerate()
+            .fold(0u8, |byte, (bit, set)| byte | ((*set as u8) << bit));
+        output.push(byte);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(data: &[u8], weights: &[u8; 256], adaptive: bool) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        encode(&mut encoded, data, weights, adaptive);
+
+        decode(
+            &mut BitReader::new(&encoded),
+            data.len() + 1,
+            weights,
+            adaptive,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_decompress_adaptive() {
+        let data = b"unit\\terran\\marine.grp unit\\terran\\marine.grp \x00\xFF\x00\xFF";
+        let compressed = compress(data);
+        assert_eq!(decompress(&compressed, data.len()).unwrap(), data.to_vec());
+        assert_eq!(decompress(&compressed, 4).unwrap(), b"unit".to_vec());
+        assert_eq!(decompress(&compressed, 0).unwrap(), Vec::<u8>::new());
+        assert!(decompress(&compressed[..4], data.len()).is_err());
+    }
+
+    #[test]
+    fn test_decompress_fixed_weights_inserts_missing_bytes() {
+        // bytes without a weight are only reachable through NEW_BYTE, and a fixed tree
+        // counts them once when they are inserted
+        let mut weights = [0; 256];
+        for (value, weight) in weights.iter_mut().enumerate().take(0x80) {
+            *weight = (value % 7 + 1) as u8;
+        }
+        let data = b"fixed \x80\x81\x80 weights \xFF\xFF";
+        assert_eq!(round_trip(data, &weights, false), data.to_vec());
+        assert_eq!(round_trip(data, &weights, true), data.to_vec());
+
+        let mut encoded = Vec::new();
+        encode(&mut encoded, data, &weights, false);
+        let adaptive = decode(&mut BitReader::new(&encoded), data.len(), &weights, true);
+        assert_ne!(adaptive.ok(), Some(data.to_vec()));
+    }
+
+    #[test]
+    fn test_decompress_unknown_type() {
+        assert!(decompress(&[0x01, 0x00], 4).is_err());
+        assert!(decompress(&[0x09, 0x00], 4).is_err());
+    }
+}
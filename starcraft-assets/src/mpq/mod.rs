@@ -0,0 +1,243 @@
+use super::errors::*;
+use super::fs::ReadSeek;
+use super::listfile::Listfile;
+use std::io::Cursor;
+
+mod adpcm;
+mod archive;
+mod attributes;
+mod compression;
+pub mod crypto;
+mod explode;
+mod huffman;
+mod reader;
+
+pub use self::archive::{ArchiveSource, Block, FileHash, Header, MPQArchive, MappedMPQArchive};
+pub use self::attributes::{Attributes, Mismatch, MismatchKind};
+pub use self::reader::FileReader;
+
+const LISTFILE_NAME: &str = "(listfile)";
+
+pub trait ReadOnlyMPQArchive {
+    /// Reads a file, failing with `ErrorKind::NotFound` if the archive does not contain it
+    fn read_file(&self, name: &str) -> Result<Vec<u8>>;
+
+    /// Opens a file to be read incrementally, reading it whole unless the archive can
+    /// stream it
+    fn open_file(&self, name: &str) -> Result<Box<dyn ReadSeek + '_>> {
+        Ok(Box::new(Cursor::new(self.read_file(name)?)))
+    }
+
+    /// Names listed in the archive's own `(listfile)`
+    fn files(&self) -> Result<Vec<String>> {
+        match self.read_file(LISTFILE_NAME) {
+            Ok(buf) => Ok(Listfile::from_buffer(&buf)
+                .iter()
+                .map(String::from)
+                .collect()),
+            Err(ref err) if err.is_not_found() => Ok(Vec::new()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Whether the archive contains a file, answered from its tables without reading
+    /// the file
+    fn exists(&self, name: &str) -> Result<bool>;
+
+    /// Uncompressed size of a file in bytes, answered from its tables without reading
+    /// the file
+    fn size(&self, name: &str) -> Result<u64>;
+}
+
+pub struct UnifiedMPQArchive<A>
+where
+    A: ReadOnlyMPQArchive,
+{
+    archives: Vec<A>,
+    listfile: Listfile,
+}
+
+impl<A> UnifiedMPQArchive<A>
+where
+    A: ReadOnlyMPQArchive,
+{
+    pub fn new() -> UnifiedMPQArchive<A> {
+        UnifiedMPQArchive {
+            archives: Vec::new(),
+            listfile: Listfile::new(),
+        }
+    }
+
+    pub fn from_existing(archives: impl IntoIterator<Item = A>) -> UnifiedMPQArchive<A> {
+        UnifiedMPQArchive {
+            archives: archives.into_iter().collect::<Vec<A>>(),
+            listfile: Listfile::new(),
+        }
+    }
+
+    pub fn add(&mut self, archive: A) {
+        self.archives.push(archive)
+    }
+
+    /// Adds the names of an external listfile, such as one shipped with a mod or tool,
+    /// for archives whose `(listfile)` is missing or incomplete
+    pub fn add_listfile(&mut self, buf: &[u8]) {
+        self.listfile.extend_from_buffer(buf)
+    }
+
+    /// Names of the files found in any archive's `(listfile)` or in an external listfile
+    pub fn files(&self) -> Result<Vec<String>> {
+        let mut listfile = Listfile::new();
+        for archive in &self.archives {
+            for file_name in archive.files()? {
+                listfile.insert(&file_name);
+            }
+        }
+        for file_name in self.listfile.iter() {
+            if !listfile.contains(file_name) && self.exists(file_name)? {
+                listfile.insert(file_name);
+            }
+        }
+
+        Ok(listfile.iter().map(String::from).collect())
+    }
+
+    pub fn exists(&self, file_name: &str) -> Result<bool> {
+        for archive in &self.archives {
+            if archive.exists(file_name)? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Size of `file_name` in the first archive that contains it, like `read_file`
+    pub fn size(&self, file_name: &str) -> Result<u64> {
+        let mut searched = Vec::with_capacity(self.archives.len());
+        for archive in &self.archives {
+            match archive.size(file_name) {
+                Ok(size) => return Ok(size),
+                Err(Error(ErrorKind::NotFound(_, archive_name), _)) => searched.push(archive_name),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(ErrorKind::NotFound(file_name.into(), searched.join(", ")).into())
+    }
+
+    /// Reads `file_name` from the first archive that contains it.
+    ///
+    /// Only a missing file moves on to the next archive; a file that exists but cannot
+    /// be read fails the lookup with that archive's error.
+    pub fn read_file(&self, file_name: &str) -> Result<Vec<u8>> {
+        let mut searched = Vec::with_capacity(self.archives.len());
+        for archive in &self.archives {
+            match archive.read_file(file_name) {
+                Ok(buf) => return Ok(buf),
+                Err(Error(ErrorKind::NotFound(_, archive_name), _)) => searched.push(archive_name),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(ErrorKind::NotFound(file_name.into(), searched.join(", ")).into())
+    }
+
+    /// Opens `file_name` from the first archive that contains it, like `read_file`
+    pub fn open_file(&self, file_name: &str) -> Result<Box<dyn ReadSeek + '_>> {
+        let mut searched = Vec::with_capacity(self.archives.len());
+        for archive in &self.archives {
+            match archive.open_file(file_name) {
+                Ok(file) => return Ok(file),
+                Err(Error(ErrorKind::NotFound(_, archive_name), _)) => searched.push(archive_name),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(ErrorKind::NotFound(file_name.into(), searched.join(", ")).into())
+    }
+}
+
+impl<A> super::fs::ReadOnlyFileSystem for UnifiedMPQArchive<A>
+where
+    A: ReadOnlyMPQArchive + Sync + Send,
+{
+    fn read(&self, file_name: &str) -> std::result::Result<Vec<u8>, Error> {
+        UnifiedMPQArchive::read_file(self, file_name)
+    }
+
+    fn open(&self, file_name: &str) -> Result<Box<dyn ReadSeek + '_>> {
+        UnifiedMPQArchive::open_file(self, file_name)
+    }
+
+    fn files(&self) -> Result<Vec<String>> {
+        UnifiedMPQArchive::files(self)
+    }
+
+    fn exists(&self, file_name: &str) -> Result<bool> {
+        UnifiedMPQArchive::exists(self, file_name)
+    }
+
+    fn size(&self, file_name: &str) -> Result<u64> {
+        UnifiedMPQArchive::size(self, file_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fs::ReadOnlyFileSystem;
+    use super::*;
+    use std::collections::HashMap;
+
+    struct Archive(HashMap<&'static str, &'static [u8]>);
+
+    impl ReadOnlyMPQArchive for Archive {
+        fn read_file(&self, name: &str) -> Result<Vec<u8>> {
+            self.0
+                .get(name)
+                .map(|buf| buf.to_vec())
+                .ok_or_else(|| ErrorKind::NotFound(name.into(), "test.mpq".into()).into())
+        }
+
+        fn exists(&self, name: &str) -> Result<bool> {
+            Ok(self.0.contains_key(name))
+        }
+
+        fn size(&self, name: &str) -> Result<u64> {
+            self.0
+                .get(name)
+                .map(|buf| buf.len() as u64)
+                .ok_or_else(|| ErrorKind::NotFound(name.into(), "test.mpq".into()).into())
+        }
+    }
+
+    #[test]
+    fn test_unified_archive_lists_files_from_internal_and_external_listfiles() {
+        let mut stardat = HashMap::new();
+        stardat.insert(LISTFILE_NAME, &b"arr\\units.dat"[..]);
+        stardat.insert("arr\\units.dat", &b"units"[..]);
+        stardat.insert("unit\\zerg\\zergling.grp", &b"zergling"[..]);
+        let mut archive = UnifiedMPQArchive::from_existing(vec![Archive(stardat)]);
+        archive.add_listfile(b"unit\\zerg\\zergling.grp\r\nunit\\zerg\\missing.grp");
+
+        assert_eq!(
+            ReadOnlyFileSystem::files(&archive).unwrap(),
+            vec!["arr\\units.dat", "unit\\zerg\\zergling.grp"]
+        );
+        assert_eq!(
+            archive.glob("unit\\zerg\\*.grp").unwrap(),
+            vec!["unit\\zerg\\zergling.grp"]
+        );
+        assert_eq!(
+            ReadOnlyFileSystem::size(&archive, "arr\\units.dat").unwrap(),
+            5
+        );
+        assert!(!ReadOnlyFileSystem::exists(&archive, "unit\\zerg\\missing.grp").unwrap());
+        assert_eq!(
+            ReadOnlyFileSystem::size(&archive, "unit\\zerg\\missing.grp")
+                .unwrap_err()
+                .to_string(),
+            "file not found: 'unit\\zerg\\missing.grp' in test.mpq"
+        );
+    }
+}
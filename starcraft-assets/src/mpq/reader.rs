@@ -0,0 +1,250 @@
+use super::super::errors::*;
+use super::{ArchiveSource, Block, FileHash, MPQArchive};
+use std::borrow::Cow;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// A file in an archive, read and decompressed one sector at a time.
+pub struct FileReader<'a, S> {
+    archive: &'a MPQArchive<S>,
+    file_name: String,
+    block: Block,
+    key: u32,
+    /// Where each sector of a compressed file starts, relative to the block
+    sector_offsets: Option<Vec<usize>>,
+    position: u64,
+    /// The last sector that was read and its index
+    sector: Option<(usize, Cow<'a, [u8]>)>,
+}
+
+impl<S> MPQArchive<S>
+where
+    S: ArchiveSource,
+{
+    /// Opens a file to be read as it is needed, rather than all at once like `read_file`
+    pub fn open_file(&self, file_name: &str) -> Result<FileReader<'_, S>> {
+        let block = *self
+            .find_block(file_name)
+            .ok_or_else(|| ErrorKind::NotFound(file_name.into(), self.name().into()))?;
+
+        FileReader::new(self, file_name.into(), block, block.key(file_name))
+    }
+
+    pub fn open_file_by_hash(&self, hash: &FileHash) -> Result<FileReader<'_, S>> {
+        let block = *self
+            .find_block_by_hash(hash)
+            .ok_or_else(|| ErrorKind::NotFound(hash.to_string(), self.name().into()))?;
+
+        FileReader::new(self, hash.to_string(), block, block.fix_key(hash.key))
+    }
+}
+
+impl<'a, S> FileReader<'a, S>
+where
+    S: ArchiveSource,
+{
+    fn new(
+        archive: &'a MPQArchive<S>,
+        file_name: String,
+        block: Block,
+        key: u32,
+    ) -> Result<FileReader<'a, S>> {
+        let offset = archive.header().offset + block.offset;
+        if offset + block.packed_size as u64 > archive.len {
+            return Err(ErrorKind::Corrupt(file_name, archive.name().into(), Some(offset)).into());
+        }
+
+        let sector_offsets = if block.is_compressed() && !block.is_single_unit() {
+            let table_size = archive.sector_table_entries(&block) * 4;
+            let table = archive
+                .read_at(offset, table_size.min(block.packed_size as usize))
+                .chain_err(|| {
+                    ErrorKind::Io(file_name.clone(), archive.name().into(), Some(offset))
+                })?;
+            if table.len() < table_size {
+                return Err(
+                    ErrorKind::Corrupt(file_name, archive.name().into(), Some(offset)).into(),
+                );
+            }
+            Some(archive.sector_offsets(
+                &file_name,
+                &block,
+                &table,
+                block.packed_size as usize,
+                key,
+            )?)
+        } else {
+            None
+        };
+
+        Ok(FileReader {
+            archive,
+            file_name,
+            block,
+            key,
+            sector_offsets,
+            position: 0,
+            sector: None,
+        })
+    }
+
+    /// Uncompressed size of the file in bytes
+    pub fn size(&self) -> u64 {
+        self.block.size as u64
+    }
+
+    /// A single unit file is one sector the size of the file
+    fn sector_size(&self) -> usize {
+        if self.block.is_single_unit() {
+            (self.block.size as usize).max(1)
+        } else {
+            self.archive.header().sector_size
+        }
+    }
+
+    fn read_sector(&self, index: usize) -> Result<Cow<'a, [u8]>> {
+        let sector_size = self.sector_size();
+        let size = sector_size.min(self.block.size as usize - index * sector_size);
+        let (start, end) = match &self.sector_offsets {
+            Some(sector_offsets) => (sector_offsets[index], sector_offsets[index + 1]),
+            None if self.block.is_single_unit() => (0, self.block.packed_size as usize),
+            None => (index * sector_size, index * sector_size + size),
+        };
+
+        let offset = self.archive.header().offset + self.block.offset + start as u64;
+        let name = || self.archive.name().to_string();
+        let sector = self
+            .archive
+            .read_at(offset, end - start)
+            .chain_err(|| ErrorKind::Io(self.file_name.clone(), name(), Some(offset)))?;
+
+        // a sector borrowed from a mapped archive stays borrowed unless it has to be decoded
+        match sector {
+            Cow::Borrowed(sector) => {
+                self.archive
+                    .decode_sector(&self.block, sector, index, self.key, size)
+            }
+            Cow::Owned(sector) => self
+                .archive
+                .decode_sector(&self.block, &sector, index, self.key, size)
+                .map(|sector| Cow::Owned(sector.into_owned())),
+        }
+        .chain_err(|| ErrorKind::Corrupt(self.file_name.clone(), name(), Some(offset)))
+    }
+}
+
+impl<'a, S> Read for FileReader<'a, S>
+where
+    S: ArchiveSource,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.size() || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let sector_size = self.sector_size();
+        let index = (self.position / sector_size as u64) as usize;
+        if self.sector.as_ref().map(|(cached, _)| *cached) != Some(index) {
+            let sector = self.read_sector(index).map_err(|err| {
+                let kind = match err.kind() {
+                    ErrorKind::Io(..) => io::ErrorKind::Other,
+                    _ => io::ErrorKind::InvalidData,
+                };
+                io::Error::new(kind, err.to_string())
+            })?;
+            self.sector = Some((index, sector));
+        }
+
+        let sector = &self.sector.as_ref().unwrap().1;
+        let start = (self.position - (index * sector_size) as u64) as usize;
+        let len = buf.len().min(sector.len().saturating_sub(start));
+        buf[..len].copy_from_slice(&sector[start..start + len]);
+        self.position += len as u64;
+
+        Ok(len)
+    }
+}
+
+impl<'a, S> Seek for FileReader<'a, S>
+where
+    S: ArchiveSource,
+{
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.size().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        }
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek before the start of the file",
+            )
+        })?;
+        self.position = position;
+
+        Ok(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::archive::tests::{build_archive, build_archive_with, zlib_sector, TestFile};
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_file_reader_reads_and_seeks_across_sectors() {
+        let sound = (0..1500u32).map(|i| (i % 11) as u8).collect::<Vec<_>>();
+        let buf = build_archive(&[TestFile {
+            name: "sound\\misc\\buzz.wav",
+            data: sound.clone(),
+            flags: Block::COMPRESS | Block::ENCRYPTED | Block::FIX_KEY,
+        }]);
+        let archive = MPQArchive::from_reader("StarDat.mpq", Cursor::new(buf)).unwrap();
+        let mut file = archive.open_file("sound\\misc\\buzz.wav").unwrap();
+
+        let mut start = [0u8; 4];
+        file.read_exact(&mut start).unwrap();
+        assert_eq!(start, sound[..4]);
+
+        file.seek(SeekFrom::Start(0x1FE)).unwrap();
+        let mut across = [0u8; 4];
+        file.read_exact(&mut across).unwrap();
+        assert_eq!(across, sound[0x1FE..0x202]);
+
+        file.seek(SeekFrom::End(-100)).unwrap();
+        let mut rest = Vec::new();
+        file.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, sound[1400..]);
+        assert!(file.seek(SeekFrom::Current(-2000)).is_err());
+        assert!(archive.open_file("sound\\misc\\missing.wav").is_err());
+    }
+
+    #[test]
+    fn test_file_reader_rejects_sectors_that_decompress_short() {
+        let sound = (0..1500u32).map(|i| (i % 11) as u8).collect::<Vec<_>>();
+        // the middle sector only holds half of its bytes
+        let buf = build_archive_with(
+            &[TestFile {
+                name: "sound\\misc\\buzz.wav",
+                data: sound,
+                flags: Block::COMPRESS,
+            }],
+            |index, sector| {
+                zlib_sector(if index == 1 {
+                    &sector[..sector.len() / 2]
+                } else {
+                    sector
+                })
+            },
+        );
+        let archive = MPQArchive::from_reader("StarDat.mpq", Cursor::new(buf)).unwrap();
+
+        let mut file = archive.open_file("sound\\misc\\buzz.wav").unwrap();
+        let mut data = Vec::new();
+        let err = file.read_to_end(&mut data).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(data.len(), 0x200);
+        assert!(archive.read_file("sound\\misc\\buzz.wav").is_err());
+    }
+}
@@ -0,0 +1,234 @@
+use super::errors::*;
+use super::fs::{ReadOnlyFileSystem, ReadSeek};
+use super::listfile::Listfile;
+
+/// Where a layer sits in the game's load order.
+///
+/// Layers with a higher priority are searched first. Layers with the same priority
+/// are searched in the order they were added.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Priority {
+    StarDat,
+    BrooDat,
+    /// patch_rt.mpq
+    Patch,
+    /// Mod archives and folders, loaded over the game data
+    Mod,
+    /// The map's own archive
+    Map,
+}
+
+pub struct Layer {
+    pub name: String,
+    pub priority: Priority,
+    fs: Box<dyn ReadOnlyFileSystem>,
+}
+
+/// A file along with the layer that served it.
+pub struct Sourced<'a> {
+    pub data: Vec<u8>,
+    pub layer: &'a Layer,
+}
+
+/// A stack of file systems that resolves each file from the highest priority layer holding it.
+pub struct OverlayFileSystem {
+    layers: Vec<Layer>,
+}
+
+impl OverlayFileSystem {
+    pub fn new() -> OverlayFileSystem {
+        OverlayFileSystem { layers: Vec::new() }
+    }
+
+    pub fn add(
+        &mut self,
+        name: impl Into<String>,
+        priority: Priority,
+        fs: impl ReadOnlyFileSystem + 'static,
+    ) {
+        let position = self
+            .layers
+            .iter()
+            .position(|layer| layer.priority < priority)
+            .unwrap_or(self.layers.len());
+
+        self.layers.insert(
+            position,
+            Layer {
+                name: name.into(),
+                priority,
+                fs: Box::new(fs),
+            },
+        );
+    }
+
+    /// Layers in the order they are searched
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// Reads `file_name` and reports which layer it came from
+    pub fn read_sourced(&self, file_name: &str) -> Result<Sourced<'_>> {
+        for layer in &self.layers {
+            match layer.fs.read(file_name) {
+                Ok(data) => return Ok(Sourced { data, layer }),
+                Err(ref err) if err.is_not_found() => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(self.not_found(file_name))
+    }
+
+    /// The error for a file that no layer holds, naming the layers that were searched
+    fn not_found(&self, file_name: &str) -> Error {
+        let searched = self
+            .layers
+            .iter()
+            .map(|layer| layer.name.as_str())
+            .collect::<Vec<_>>();
+
+        ErrorKind::NotFound(file_name.into(), searched.join(", ")).into()
+    }
+}
+
+impl Default for OverlayFileSystem {
+    fn default() -> Self {
+        OverlayFileSystem::new()
+    }
+}
+
+impl ReadOnlyFileSystem for OverlayFileSystem {
+    fn read(&self, file_name: &str) -> Result<Vec<u8>> {
+        self.read_sourced(file_name).map(|sourced| sourced.data)
+    }
+
+    fn open(&self, file_name: &str) -> Result<Box<dyn ReadSeek + '_>> {
+        for layer in &self.layers {
+            match layer.fs.open(file_name) {
+                Ok(file) => return Ok(file),
+                Err(ref err) if err.is_not_found() => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(self.not_found(file_name))
+    }
+
+    fn files(&self) -> Result<Vec<String>> {
+        let mut listfile = Listfile::new();
+        for layer in &self.layers {
+            for file_name in layer.fs.files()? {
+                listfile.insert(&file_name);
+            }
+        }
+
+        Ok(listfile.iter().map(String::from).collect())
+    }
+
+    fn exists(&self, file_name: &str) -> Result<bool> {
+        for layer in &self.layers {
+            if layer.fs.exists(file_name)? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn size(&self, file_name: &str) -> Result<u64> {
+        for layer in &self.layers {
+            match layer.fs.size(file_name) {
+                Ok(size) => return Ok(size),
+                Err(ref err) if err.is_not_found() => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(self.not_found(file_name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct SingleFile(&'static str, &'static [u8]);
+
+    impl ReadOnlyFileSystem for SingleFile {
+        fn read(&self, file_name: &str) -> Result<Vec<u8>> {
+            if file_name == self.0 {
+                Ok(self.1.to_vec())
+            } else {
+                Err(ErrorKind::NotFound(file_name.into(), "test".into()).into())
+            }
+        }
+
+        fn exists(&self, file_name: &str) -> Result<bool> {
+            Ok(file_name == self.0)
+        }
+
+        fn size(&self, file_name: &str) -> Result<u64> {
+            self.read(file_name).map(|data| data.len() as u64)
+        }
+    }
+
+    #[test]
+    fn test_overlay_file_system_prefers_higher_priority_layers() {
+        let mut fs = OverlayFileSystem::new();
+        fs.add(
+            "StarDat.mpq",
+            Priority::StarDat,
+            SingleFile("a", b"stardat"),
+        );
+        fs.add("map.scx", Priority::Map, SingleFile("a", b"map"));
+        fs.add(
+            "BrooDat.mpq",
+            Priority::BrooDat,
+            SingleFile("b", b"broodat"),
+        );
+        fs.add("mod", Priority::Mod, SingleFile("b", b"mod"));
+        fs.add(
+            "StarDat-2.mpq",
+            Priority::StarDat,
+            SingleFile("c", b"stardat"),
+        );
+
+        let names = fs
+            .layers()
+            .iter()
+            .map(|l| l.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "map.scx",
+                "mod",
+                "BrooDat.mpq",
+                "StarDat.mpq",
+                "StarDat-2.mpq"
+            ]
+        );
+
+        let a = fs.read_sourced("a").unwrap();
+        assert_eq!(
+            (a.data.as_slice(), a.layer.priority),
+            (&b"map"[..], Priority::Map)
+        );
+        assert_eq!(fs.read_sourced("b").unwrap().layer.name, "mod");
+        assert_eq!(fs.read("c").unwrap(), b"stardat".to_vec());
+        let searched = "map.scx, mod, BrooDat.mpq, StarDat.mpq, StarDat-2.mpq";
+        for err in [
+            fs.read("d").unwrap_err(),
+            fs.open("d").err().unwrap(),
+            fs.size("d").unwrap_err(),
+        ] {
+            match err.kind() {
+                ErrorKind::NotFound(file_name, layers) => {
+                    assert_eq!((file_name.as_str(), layers.as_str()), ("d", searched))
+                }
+                kind => panic!("unexpected error: {}", kind),
+            }
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use super::errors::*;
+use super::wpe::{WPEs, WPE};
+use byteorder::{ByteOrder, LittleEndian};
+use std::io::{Cursor, Write};
+
+/// An 8-bit run length encoded PCX image with a 256-color palette, as used for the UI
+/// art and the remapping tables.
+#[derive(Debug, Clone)]
+pub struct PCX {
+    pub width: usize,
+    pub height: usize,
+    /// Palette indices, row by row
+    pub pixels: Vec<u8>,
+    pub palette: WPEs,
+}
+
+impl PCX {
+    const HEADER_SIZE: usize = 128;
+    const MANUFACTURER: u8 = 0x0A;
+    const VERSION: u8 = 5;
+    const RLE: u8 = 1;
+    const PALETTE_MARKER: u8 = 0x0C;
+    const PALETTE_SIZE: usize = 256 * 3;
+    /// Bytes with both high bits set start a run of the next byte
+    const RUN: u8 = 0xC0;
+    const MAX_RUN: usize = 0x3F;
+
+    pub fn from_buffer(cursor: &mut Cursor<&Vec<u8>>) -> Result<PCX> {
+        let start = cursor.position() as usize;
+        let buf = cursor
+            .get_ref()
+            .get(start..)
+            .filter(|buf| buf.len() >= PCX::HEADER_SIZE)
+            .ok_or_else(|| format!("failed to read pcx header at position: '{}'", start))?;
+
+        let (manufacturer, encoding, bits_per_pixel, planes) = (buf[0], buf[2], buf[3], buf[65]);
+        if manufacturer != PCX::MANUFACTURER || encoding != PCX::RLE {
+            return Err("not a run length encoded pcx".into());
+        }
+        if bits_per_pixel != 8 || planes != 1 {
+            return Err(format!(
+                "unsupported pcx with {} planes of {} bits per pixel",
+                planes, bits_per_pixel
+            )
+            .into());
+        }
+
+        let x_min = LittleEndian::read_u16(&buf[4..]) as usize;
+        let y_min = LittleEndian::read_u16(&buf[6..]) as usize;
+        let x_max = LittleEndian::read_u16(&buf[8..]) as usize;
+        let y_max = LittleEndian::read_u16(&buf[10..]) as usize;
+        let bytes_per_line = LittleEndian::read_u16(&buf[66..]) as usize;
+        let width = (x_max + 1)
+            .checked_sub(x_min)
+            .ok_or("pcx has a negative width")?;
+        let height = (y_max + 1)
+            .checked_sub(y_min)
+            .ok_or("pcx has a negative height")?;
+        if bytes_per_line < width {
+            return Err(format!(
+                "pcx lines of {} bytes cannot hold {} pixels",
+                bytes_per_line, width
+            )
+            .into());
+        }
+
+        // lines are padded to `bytes_per_line`, and runs may cross into the next line
+        let mut lines = Vec::with_capacity(bytes_per_line * height);
+        let mut position = PCX::HEADER_SIZE;
+        while lines.len() < bytes_per_line * height {
+            let byte = *buf.get(position).ok_or_else(|| {
+                format!("pcx image data ends early at position {}", start + position)
+            })?;
+            position += 1;
+
+            if byte & PCX::RUN == PCX::RUN {
+                let value = *buf.get(position).ok_or_else(|| {
+                    format!("pcx image data ends early at position {}", start + position)
+                })?;
+                position += 1;
+                lines.extend(std::iter::repeat_n(value, (byte & !PCX::RUN) as usize));
+            } else {
+                lines.push(byte);
+            }
+        }
+        lines.truncate(bytes_per_line * height);
+        let pixels = lines
+            .chunks_exact(bytes_per_line.max(1))
+            .flat_map(|line| line[..width].iter().copied())
+            .collect::<Vec<_>>();
+
+        let palette_start = buf.len().checked_sub(PCX::PALETTE_SIZE + 1);
+        let palette = match palette_start {
+            Some(palette_start)
+                if palette_start >= position && buf[palette_start] == PCX::PALETTE_MARKER =>
+            {
+                &buf[palette_start + 1..]
+            }
+            _ => return Err("pcx has no 256-color palette".into()),
+        };
+        cursor.set_position(cursor.get_ref().len() as u64);
+
+        Ok(PCX {
+            width,
+            height,
+            pixels,
+            palette: WPEs(
+                palette
+                    .chunks_exact(3)
+                    .map(|color| WPE([color[0], color[1], color[2]]))
+                    .collect(),
+            ),
+        })
+    }
+
+    /// Row `y` of palette indices. Each row of a remapping table such as `ofire.pcx`
+    /// maps every palette index to its replacement. `None` if `y` is past the last row.
+    pub fn row(&self, y: usize) -> Option<&[u8]> {
+        if y >= self.height {
+            return None;
+        }
+
+        self.pixels.get(y * self.width..(y + 1) * self.width)
+    }
+
+    /// Writes the image as a PCX the game can read
+    pub fn write(&self, writer: &mut impl Write) -> Result<()> {
+        if self.pixels.len() != self.width * self.height
+            || self.width == 0
+            || self.height == 0
+            || self.width > 0xFFFE
+            || self.height > 0x10000
+        {
+            return Err(format!("invalid {}x{} pcx", self.width, self.height).into());
+        }
+        if self.palette.0.len() > 256 {
+            return Err("pcx palettes hold at most 256 colors".into());
+        }
+
+        // lines must have an even length
+        let bytes_per_line = self.width + self.width % 2;
+        let mut header = [0u8; PCX::HEADER_SIZE];
+        header[0] = PCX::MANUFACTURER;
+        header[1] = PCX::VERSION;
+        header[2] = PCX::RLE;
+        header[3] = 8;
+        LittleEndian::write_u16(&mut header[8..], (self.width - 1) as u16);
+        LittleEndian::write_u16(&mut header[10..], (self.height - 1) as u16);
+        LittleEndian::write_u16(&mut header[12..], 72);
+        LittleEndian::write_u16(&mut header[14..], 72);
+        header[65] = 1;
+        LittleEndian::write_u16(&mut header[66..], bytes_per_line as u16);
+        // color palette
+        LittleEndian::write_u16(&mut header[68..], 1);
+
+        let mut buf = header.to_vec();
+        for row in self.pixels.chunks_exact(self.width) {
+            let mut line = row.to_vec();
+            line.resize(bytes_per_line, 0);
+
+            let mut i = 0;
+            while i < line.len() {
+                let value = line[i];
+                let run = line[i..]
+                    .iter()
+                    .take(PCX::MAX_RUN)
+                    .take_while(|byte| **byte == value)
+                    .count();
+                if run > 1 || value & PCX::RUN == PCX::RUN {
+                    buf.push(PCX::RUN | run as u8);
+                }
+                buf.push(value);
+                i += run;
+            }
+        }
+
+        buf.push(PCX::PALETTE_MARKER);
+        for i in 0..256 {
+            let color = self.palette.0.get(i).map(|wpe| wpe.0).unwrap_or_default();
+            buf.extend_from_slice(&color);
+        }
+
+        writer.write_all(&buf).chain_err(|| "failed to write pcx")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pcx_round_trips_runs_and_odd_widths() {
+        let pcx = PCX {
+            width: 3,
+            height: 2,
+            pixels: vec![7, 7, 0xC5, 1, 2, 3],
+            palette: WPEs((0..=255u8).map(|i| WPE([i, 0, 255 - i])).collect()),
+        };
+        let mut buf = Vec::new();
+        pcx.write(&mut buf).unwrap();
+
+        // a run of two 7s, an escaped 0xC5, then the pad byte
+        assert_eq!(
+            buf[PCX::HEADER_SIZE..PCX::HEADER_SIZE + 5],
+            [0xC2, 7, 0xC1, 0xC5, 0]
+        );
+
+        let mut cursor = Cursor::new(&buf);
+        let read = PCX::from_buffer(&mut cursor).unwrap();
+        assert_eq!((read.width, read.height), (3, 2));
+        assert_eq!(read.pixels, pcx.pixels);
+        assert_eq!(read.row(1), Some(&[1, 2, 3][..]));
+        assert_eq!(read.row(read.height), None);
+        assert_eq!(read.palette[200].0, [200, 0, 55]);
+        assert_eq!(cursor.position(), buf.len() as u64);
+
+        buf.truncate(buf.len() - 1);
+        assert!(PCX::from_buffer(&mut Cursor::new(&buf)).is_err());
+    }
+}
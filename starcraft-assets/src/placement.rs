@@ -0,0 +1,363 @@
+use super::chk;
+use super::map::Map;
+use super::Assets;
+
+/// Placement boxes and psi fields of preplaced units.
+pub trait UnitFootprints {
+    /// Width and height in tiles of the area that unit `unit_id` occupies
+    fn footprint(&self, unit_id: u16) -> Option<(usize, usize)>;
+
+    /// Whether unit `unit_id` powers the tiles around it for Protoss buildings
+    fn provides_power(&self, unit_id: u16) -> bool {
+        unit_id == ResourceFootprints::PYLON
+    }
+}
+
+/// Footprints of the units that most maps preplace: resources and pylons.
+pub struct ResourceFootprints;
+
+impl ResourceFootprints {
+    const PYLON: u16 = 156;
+    const MINERAL_FIELDS: [u16; 3] = [176, 177, 178];
+    const VESPENE_GEYSER: u16 = 188;
+}
+
+impl UnitFootprints for ResourceFootprints {
+    fn footprint(&self, unit_id: u16) -> Option<(usize, usize)> {
+        match unit_id {
+            ResourceFootprints::PYLON => Some((2, 2)),
+            id if ResourceFootprints::MINERAL_FIELDS.contains(&id) => Some((2, 1)),
+            ResourceFootprints::VESPENE_GEYSER => Some((4, 2)),
+            _ => None,
+        }
+    }
+}
+
+/// What a building needs from the tiles it is placed on.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Requirement {
+    /// Terran buildings: any buildable tile without creep
+    None,
+    /// Zerg buildings: every tile must have creep
+    Creep,
+    /// Protoss buildings: no creep, and the center tile must be powered
+    Power,
+}
+
+/// What prevents building on an otherwise buildable tile.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Blocker {
+    /// Index into `Map::units`
+    Unit(usize),
+    /// Index into `Map::doodads`
+    Doodad(usize),
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BuildTile {
+    buildable: bool,
+    creep: bool,
+    walkable: bool,
+    pub powered: bool,
+    pub blocker: Option<Blocker>,
+}
+
+impl BuildTile {
+    /// Whether the terrain allows buildings, ignoring units and doodads
+    pub fn is_terrain_buildable(&self) -> bool {
+        self.buildable && self.walkable
+    }
+
+    pub fn is_buildable(&self) -> bool {
+        self.is_terrain_buildable() && self.blocker.is_none()
+    }
+
+    pub fn has_creep(&self) -> bool {
+        self.creep
+    }
+
+    pub fn is_blocked(&self) -> bool {
+        self.blocker.is_some()
+    }
+}
+
+/// Per-tile building placement data of a map.
+pub struct BuildGrid {
+    pub width: usize,
+    pub height: usize,
+    tiles: Vec<BuildTile>,
+}
+
+impl BuildGrid {
+    /// Psi field of a pylon in tiles, centered on the pylon's tile
+    const PSI_FIELD: [[u8; 16]; 10] = [
+        [0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0],
+        [0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0],
+        [0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0],
+        [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+        [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+        [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+        [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+        [0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0],
+        [0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0],
+        [0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0],
+    ];
+
+    pub fn from_map(map: &Map, assets: &Assets, footprints: &dyn UnitFootprints) -> BuildGrid {
+        BuildGrid::new(
+            &map.dimensions,
+            &map.mega_tile_ids,
+            &map.doodads,
+            &map.units,
+            assets,
+            footprints,
+        )
+    }
+
+    pub fn new(
+        dimensions: &chk::Dimensions,
+        megatiles: &[chk::MegaTileID],
+        doodads: &[chk::Doodad],
+        units: &[chk::Unit],
+        assets: &Assets,
+        footprints: &dyn UnitFootprints,
+    ) -> BuildGrid {
+        let mut grid = BuildGrid {
+            width: dimensions.width,
+            height: dimensions.height,
+            tiles: megatiles
+                .iter()
+                .take(dimensions.width * dimensions.height)
+                .map(|megatile| {
+                    // tiles outside of the tileset can be neither built on nor walked on
+                    let group = match assets.cv5s.0.get(megatile.group_index()) {
+                        Some(group) => group,
+                        None => return BuildTile::default(),
+                    };
+                    let megatile_id = group[megatile.subtile_index()] as usize;
+                    let walkable = assets
+                        .vf4s
+                        .0
+                        .get(megatile_id)
+                        .map(|minitiles| minitiles.iter().any(|m| m.is_walkable()));
+                    BuildTile {
+                        buildable: walkable.is_some() && group.is_buildable(),
+                        creep: group.has_creep(),
+                        walkable: walkable.unwrap_or(false),
+                        ..BuildTile::default()
+                    }
+                })
+                .collect(),
+        };
+        grid.tiles
+            .resize(grid.width * grid.height, BuildTile::default());
+
+        for (i, doodad) in doodads.iter().enumerate() {
+            if doodad.disabled {
+                continue;
+            }
+            if let Some((_, cv5_doodad)) = assets.cv5s.find_doodad(doodad.id) {
+                let (width, height) = (cv5_doodad.width as usize, cv5_doodad.height as usize);
+                for (x, y) in grid.footprint_tiles(doodad.x, doodad.y, width, height) {
+                    grid.tiles[x + y * grid.width].blocker = Some(Blocker::Doodad(i));
+                }
+            }
+        }
+
+        for (i, unit) in units.iter().enumerate() {
+            if let Some((width, height)) = footprints.footprint(unit.id) {
+                for (x, y) in grid.footprint_tiles(unit.x, unit.y, width, height) {
+                    grid.tiles[x + y * grid.width].blocker = Some(Blocker::Unit(i));
+                }
+            }
+            if footprints.provides_power(unit.id) {
+                grid.add_psi_field(unit.x as usize / 32, unit.y as usize / 32);
+            }
+        }
+
+        grid
+    }
+
+    pub fn tile(&self, x: usize, y: usize) -> Option<&BuildTile> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        self.tiles.get(x + y * self.width)
+    }
+
+    /// Whether a `width`x`height` building fits with its top left tile at (`x`, `y`)
+    pub fn can_place(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        requirement: Requirement,
+    ) -> bool {
+        if width == 0 || height == 0 || x + width > self.width || y + height > self.height {
+            return false;
+        }
+
+        let footprint_is_valid = (y..y + height)
+            .flat_map(|ty| (x..x + width).map(move |tx| (tx, ty)))
+            .all(|(tx, ty)| {
+                let tile = &self.tiles[tx + ty * self.width];
+                tile.is_buildable() && tile.creep == (requirement == Requirement::Creep)
+            });
+
+        match requirement {
+            Requirement::Power => {
+                footprint_is_valid
+                    && self.tiles[x + width / 2 + (y + height / 2) * self.width].powered
+            }
+            _ => footprint_is_valid,
+        }
+    }
+
+    /// Tiles covered by a `width`x`height` area centered at pixel (`x`, `y`), clipped to the map
+    fn footprint_tiles(
+        &self,
+        x: u16,
+        y: u16,
+        width: usize,
+        height: usize,
+    ) -> impl Iterator<Item = (usize, usize)> {
+        let left = (x as isize - (width * 16) as isize).max(0) as usize / 32;
+        let top = (y as isize - (height * 16) as isize).max(0) as usize / 32;
+        let right = (left + width).min(self.width);
+        let bottom = (top + height).min(self.height);
+
+        (top..bottom).flat_map(move |ty| (left..right).map(move |tx| (tx, ty)))
+    }
+
+    fn add_psi_field(&mut self, pylon_x: usize, pylon_y: usize) {
+        for (row, mask) in BuildGrid::PSI_FIELD.iter().enumerate() {
+            for (column, powered) in mask.iter().enumerate() {
+                let x = (pylon_x + column).checked_sub(8);
+                let y = (pylon_y + row).checked_sub(5);
+                if let (Some(x), Some(y), 1) = (x, y, powered) {
+                    if x < self.width && y < self.height {
+                        self.tiles[x + y * self.width].powered = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{cv5, loader, vf4, vr4, vx4, wpe};
+    use super::*;
+    use std::io::Cursor;
+
+    fn assets() -> Assets {
+        // group 0: buildable, group 1: unbuildable, group 2: creep, group 3: a MegaTile
+        // missing from the VF4, group 1024: a 2x1 doodad with id 1
+        let mut cv5_buf = vec![0u8; 52 * 1025];
+        cv5_buf[52 + 2] = 0x80;
+        cv5_buf[104 + 2] = 0x40;
+        cv5_buf[156 + 20] = 7;
+        let doodad = 52 * 1024;
+        cv5_buf[doodad + 12] = 1;
+        cv5_buf[doodad + 14] = 2;
+        cv5_buf[doodad + 16] = 1;
+        let vf4_buf = vec![1u8; 32];
+
+        Assets {
+            tileset: loader::Tileset::Badlands,
+            cv5s: cv5::CV5s::from_buffer(&mut Cursor::new(&cv5_buf)).unwrap(),
+            vf4s: vf4::VF4s::from_buffer(&mut Cursor::new(&vf4_buf)).unwrap(),
+            vx4s: vx4::VX4s(vec![]),
+            vr4s: vr4::VR4s(vec![]),
+            wpes: wpe::WPEs(vec![]),
+        }
+    }
+
+    fn megatiles(groups: &[u16]) -> Vec<chk::MegaTileID> {
+        let buf = groups
+            .iter()
+            .flat_map(|group| (group << 4).to_le_bytes().to_vec())
+            .collect::<Vec<u8>>();
+        let mut cursor = Cursor::new(&buf);
+
+        groups
+            .iter()
+            .map(|_| chk::MegaTileID::from_buffer(&mut cursor).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_build_grid_checks_terrain_creep_and_units() {
+        let dimensions = chk::Dimensions {
+            width: 4,
+            height: 3,
+        };
+        #[rustfmt::skip]
+        let megatiles = megatiles(&[
+            0, 0, 0, 1,
+            0, 0, 0, 0,
+            2, 2, 0, 0,
+        ]);
+        let units = vec![chk::Unit {
+            serial: 0,
+            x: 96,
+            y: 48,
+            id: 176,
+            owner: 11,
+            resources: 1500,
+        }];
+
+        let grid = BuildGrid::new(
+            &dimensions,
+            &megatiles,
+            &[],
+            &units,
+            &assets(),
+            &ResourceFootprints,
+        );
+
+        assert!(grid.can_place(0, 0, 2, 2, Requirement::None));
+        assert!(!grid.can_place(2, 0, 2, 1, Requirement::None));
+        assert_eq!(grid.tile(2, 1).unwrap().blocker, Some(Blocker::Unit(0)));
+        assert!(!grid.tile(3, 0).unwrap().is_terrain_buildable());
+        assert!(grid.can_place(0, 2, 2, 1, Requirement::Creep));
+        assert!(!grid.can_place(0, 1, 2, 2, Requirement::Creep));
+        assert!(!grid.can_place(0, 1, 2, 2, Requirement::None));
+        assert!(!grid.can_place(0, 0, 2, 2, Requirement::Power));
+        assert!(!grid.can_place(3, 2, 2, 1, Requirement::None));
+    }
+
+    #[test]
+    fn test_build_grid_records_doodads_and_unknown_tiles() {
+        // a group outside of the tileset, a MegaTile outside of the VF4 and a doodad on
+        // buildable ground
+        let doodads = vec![chk::Doodad {
+            id: 1,
+            x: 96,
+            y: 16,
+            owner: 0,
+            disabled: false,
+        }];
+        let grid = BuildGrid::new(
+            &chk::Dimensions {
+                width: 5,
+                height: 1,
+            },
+            &megatiles(&[2000, 3, 0, 0, 0]),
+            &doodads,
+            &[],
+            &assets(),
+            &ResourceFootprints,
+        );
+
+        assert!(!grid.tile(0, 0).unwrap().is_terrain_buildable());
+        assert!(!grid.tile(1, 0).unwrap().is_terrain_buildable());
+        assert!(grid.tile(2, 0).unwrap().is_terrain_buildable());
+        assert_eq!(grid.tile(2, 0).unwrap().blocker, Some(Blocker::Doodad(0)));
+        assert_eq!(grid.tile(3, 0).unwrap().blocker, Some(Blocker::Doodad(0)));
+        assert!(!grid.can_place(2, 0, 1, 1, Requirement::None));
+        assert!(grid.can_place(4, 0, 1, 1, Requirement::None));
+    }
+}
@@ -0,0 +1,243 @@
+use super::errors::*;
+use byteorder::{ByteOrder, LittleEndian};
+use std::io::{Cursor, Write};
+use std::ops::Index;
+
+/// A string table such as `rez\stat_txt.tbl`.
+///
+/// Strings are stored in the game's single byte code page; each byte is read as the
+/// character with the same code point.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct TBL(pub Vec<String>);
+
+impl Index<usize> for TBL {
+    type Output = str;
+
+    fn index(&self, i: usize) -> &Self::Output {
+        &self.0[i]
+    }
+}
+
+impl TBL {
+    pub fn from_buffer(cursor: &mut Cursor<&Vec<u8>>) -> Result<TBL> {
+        let start = cursor.position() as usize;
+        let buf = cursor
+            .get_ref()
+            .get(start..)
+            .filter(|buf| buf.len() >= 2)
+            .ok_or_else(|| format!("failed to read tbl at position: '{}'", start))?;
+
+        let count = LittleEndian::read_u16(buf) as usize;
+        let offsets = buf
+            .get(2..2 + count * 2)
+            .ok_or_else(|| format!("tbl offsets of {} strings are cut off", count))?;
+
+        let mut end = 2 + count * 2;
+        let mut strings = Vec::with_capacity(count);
+        for (i, offset) in offsets.chunks_exact(2).enumerate() {
+            let offset = LittleEndian::read_u16(offset) as usize;
+            let bytes = buf
+                .get(offset..)
+                .ok_or_else(|| format!("tbl string {} is out of bounds at {}", i, offset))?;
+            let len = bytes
+                .iter()
+                .position(|byte| *byte == 0)
+                .unwrap_or(bytes.len());
+
+            strings.push(bytes[..len].iter().map(|byte| *byte as char).collect());
+            end = end.max(offset + len + 1).min(buf.len());
+        }
+        cursor.set_position((start + end) as u64);
+
+        Ok(TBL(strings))
+    }
+
+    /// The string at `index`, counting from 0
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.0.get(index).map(String::as_str)
+    }
+
+    /// The string a DAT file refers to by `id`. Ids count from 1, and 0 means no string.
+    pub fn by_id(&self, id: u16) -> Option<&str> {
+        (id as usize)
+            .checked_sub(1)
+            .and_then(|index| self.get(index))
+    }
+
+    /// Writes the table, sharing the storage of identical strings like the game's tables
+    pub fn write(&self, writer: &mut impl Write) -> Result<()> {
+        if self.0.len() > u16::MAX as usize {
+            return Err(format!("tbl cannot hold {} strings", self.0.len()).into());
+        }
+
+        let mut data = Vec::new();
+        let mut offsets = Vec::with_capacity(self.0.len());
+        let data_start = 2 + self.0.len() * 2;
+        for (i, string) in self.0.iter().enumerate() {
+            let duplicate = self.0[..i].iter().position(|other| other == string);
+            let offset = match duplicate {
+                Some(duplicate) => offsets[duplicate],
+                None => {
+                    let offset = data_start + data.len();
+                    for c in string.chars() {
+                        if c as u32 > 0xFF || c == '\0' {
+                            return Err(format!(
+                                "tbl string {} cannot be encoded: {:?}",
+                                i, string
+                            )
+                            .into());
+                        }
+                        data.push(c as u8);
+                    }
+                    data.push(0);
+                    offset
+                }
+            };
+            if offset > u16::MAX as usize {
+                return Err("tbl strings exceed 64 KiB".into());
+            }
+            offsets.push(offset);
+        }
+
+        let mut buf = Vec::with_capacity(data_start + data.len());
+        buf.extend_from_slice(&(self.0.len() as u16).to_le_bytes());
+        for offset in offsets {
+            buf.extend_from_slice(&(offset as u16).to_le_bytes());
+        }
+        buf.extend(data);
+
+        writer.write_all(&buf).chain_err(|| "failed to write tbl")
+    }
+}
+
+/// The key that presses a button, from the prefix of its string.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Hotkey {
+    pub key: char,
+    /// How the rest of the string is shown, such as a plain tooltip or one with costs
+    pub kind: u8,
+}
+
+impl Hotkey {
+    /// Splits a button string such as `m\x01\x03M\x04ove` into its hotkey and the text
+    pub fn split(string: &str) -> Option<(Hotkey, &str)> {
+        let mut chars = string.char_indices();
+        let (_, key) = chars.next()?;
+        let (kind_index, kind) = chars.next()?;
+        if key.is_control() || !('\x01'..='\x05').contains(&kind) {
+            return None;
+        }
+
+        Some((
+            Hotkey {
+                key,
+                kind: kind as u8,
+            },
+            &string[kind_index + 1..],
+        ))
+    }
+}
+
+/// A run of text drawn in one color.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Span {
+    /// The color code in effect, where `Span::DEFAULT_COLOR` is the default
+    pub color: u8,
+    pub text: String,
+}
+
+impl Span {
+    pub const DEFAULT_COLOR: u8 = 0x01;
+    const TAB: char = '\x09';
+    const NEW_LINE: char = '\x0A';
+    const RIGHT_ALIGN: char = '\x12';
+    const CENTER: char = '\x13';
+
+    /// Splits a string at its color codes. Tabs and line breaks are kept in the text,
+    /// alignment codes are dropped.
+    pub fn parse(string: &str) -> Vec<Span> {
+        let mut spans = Vec::new();
+        let mut span = Span {
+            color: Span::DEFAULT_COLOR,
+            text: String::new(),
+        };
+        for c in string.chars() {
+            match c {
+                Span::TAB | Span::NEW_LINE => span.text.push(c),
+                Span::RIGHT_ALIGN | Span::CENTER => {}
+                '\x01'..='\x1F' => {
+                    let color = c as u8;
+                    if !span.text.is_empty() {
+                        spans.push(span);
+                    }
+                    span = Span {
+                        color,
+                        text: String::new(),
+                    };
+                }
+                _ => span.text.push(c),
+            }
+        }
+        if !span.text.is_empty() {
+            spans.push(span);
+        }
+
+        spans
+    }
+
+    /// The text of a string without any of its codes
+    pub fn strip(string: &str) -> String {
+        Span::parse(string)
+            .into_iter()
+            .map(|span| span.text)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tbl_round_trips_and_decodes_codes() {
+        let tbl = TBL(vec![
+            "Terran Marine".into(),
+            "m\x01\x03M\x04ove".into(),
+            "Terran Marine".into(),
+            "\x06Not enough minerals\x0A\x13\x04Mine more".into(),
+            "Caf\u{e9}".into(),
+        ]);
+        let mut buf = Vec::new();
+        tbl.write(&mut buf).unwrap();
+        // the duplicate name shares the first one's storage
+        assert_eq!(buf[2..4], buf[6..8]);
+
+        let mut cursor = Cursor::new(&buf);
+        let read = TBL::from_buffer(&mut cursor).unwrap();
+        assert_eq!(read, tbl);
+        assert_eq!(cursor.position(), buf.len() as u64);
+        assert_eq!(read.by_id(1), Some("Terran Marine"));
+        assert_eq!(read.by_id(0), None);
+        assert_eq!(&read[4], "Caf\u{e9}");
+
+        let (hotkey, text) = Hotkey::split(&read[1]).unwrap();
+        assert_eq!((hotkey.key, hotkey.kind), ('m', 1));
+        assert_eq!(Span::strip(text), "Move");
+        assert_eq!(Hotkey::split(&read[0]), None);
+
+        assert_eq!(
+            Span::parse(&read[3]),
+            vec![
+                Span {
+                    color: 0x06,
+                    text: "Not enough minerals\n".into()
+                },
+                Span {
+                    color: 0x04,
+                    text: "Mine more".into()
+                },
+            ]
+        );
+        assert!(TBL(vec!["\u{2603}".into()]).write(&mut Vec::new()).is_err());
+    }
+}
@@ -1,11 +1,11 @@
 use super::errors::*;
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{ByteOrder, LittleEndian};
 use std::io::Cursor;
-use std::mem::{size_of, MaybeUninit};
+use std::mem::size_of;
 use std::ops::{Index, IndexMut};
 
 /// MiniTile graphic references for each MegaTile. Referenced by CV5.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct VF4 {
     value: u16,
 }
@@ -64,24 +64,25 @@ impl VF4s {
     const BLOCK_SIZE: usize = 16;
 
     pub fn from_buffer(cursor: &mut Cursor<&Vec<u8>>) -> Result<VF4s> {
-        let buf_size = cursor.get_ref().len();
-        let out_size = buf_size / (VF4s::BLOCK_SIZE * size_of::<u16>());
-        let mut vf4s: Vec<[VF4; VF4s::BLOCK_SIZE]> = Vec::with_capacity(out_size);
-
-        vf4s.resize(out_size, unsafe { MaybeUninit::uninit().assume_init() });
-        let mut out_bytes: [u16; VF4s::BLOCK_SIZE] = unsafe { MaybeUninit::uninit().assume_init() };
-        for i in 0..out_size {
-            let previous_position = cursor.position();
-            cursor
-                .read_u16_into::<LittleEndian>(&mut out_bytes)
-                .chain_err(|| format!("failed to read vf4 at position: '{}'", previous_position))?;
-            for j in 0..VF4s::BLOCK_SIZE {
-                vf4s[i][j] = VF4 {
-                    value: out_bytes[j],
-                }
+        let block_bytes = VF4s::BLOCK_SIZE * size_of::<u16>();
+        let position = cursor.position() as usize;
+        let buf = cursor
+            .get_ref()
+            .get(position..)
+            .ok_or_else(|| format!("failed to read vf4 at position: '{}'", position))?;
+
+        let mut vf4s: Vec<[VF4; VF4s::BLOCK_SIZE]> = Vec::with_capacity(buf.len() / block_bytes);
+        for block in buf.chunks_exact(block_bytes) {
+            let mut vf4 = [VF4::default(); VF4s::BLOCK_SIZE];
+            for (j, flags) in block.chunks_exact(size_of::<u16>()).enumerate() {
+                vf4[j] = VF4 {
+                    value: LittleEndian::read_u16(flags),
+                };
             }
+            vf4s.push(vf4);
         }
+        cursor.set_position((position + vf4s.len() * block_bytes) as u64);
 
-        return Ok(VF4s(vf4s));
+        Ok(VF4s(vf4s))
     }
 }
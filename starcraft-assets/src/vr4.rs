@@ -1,9 +1,9 @@
 use super::errors::*;
-use std::io::{Cursor, Read};
-use std::mem::MaybeUninit;
+use std::io::Cursor;
 use std::ops::{Index, IndexMut};
 
-pub type VR4 = [usize; VR4s::BLOCK_SIZE];
+/// An 8x8 MiniTile bitmap of WPE palette indices.
+pub type VR4 = [u8; VR4s::BLOCK_SIZE];
 
 #[derive(Clone)]
 pub struct VR4s(pub Vec<VR4>);
@@ -26,24 +26,39 @@ impl VR4s {
     pub const BLOCK_SIZE: usize = 64;
 
     pub fn from_buffer(cursor: &mut Cursor<&Vec<u8>>) -> Result<VR4s> {
-        let buf_size = cursor.get_ref().len();
-        let out_size = buf_size / VR4s::BLOCK_SIZE;
-
-        let mut vr4s: Vec<VR4> = Vec::with_capacity(out_size);
-        vr4s.resize(out_size, unsafe { MaybeUninit::uninit().assume_init() });
-
-        let mut out_bytes: [u8; VR4s::BLOCK_SIZE] = unsafe { MaybeUninit::uninit().assume_init() };
-        for i in 0..out_size {
-            let previous_position = cursor.position();
-            cursor.read(&mut out_bytes).chain_err(|| {
-                format!("failed to read vr4s at position: '{}'", previous_position)
-            })?;
-
-            for j in 0..VR4s::BLOCK_SIZE {
-                vr4s[i][j] = out_bytes[j] as usize;
-            }
+        let position = cursor.position() as usize;
+        let buf = cursor
+            .get_ref()
+            .get(position..)
+            .ok_or_else(|| format!("failed to read vr4s at position: '{}'", position))?;
+
+        let mut vr4s: Vec<VR4> = Vec::with_capacity(buf.len() / VR4s::BLOCK_SIZE);
+        for block in buf.chunks_exact(VR4s::BLOCK_SIZE) {
+            let mut vr4 = [0u8; VR4s::BLOCK_SIZE];
+            vr4.copy_from_slice(block);
+            vr4s.push(vr4);
         }
+        cursor.set_position((position + vr4s.len() * VR4s::BLOCK_SIZE) as u64);
+
+        Ok(VR4s(vr4s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vr4s_ignore_trailing_partial_block() {
+        let buf = (0..VR4s::BLOCK_SIZE * 2 + 5)
+            .map(|i| i as u8)
+            .collect::<Vec<u8>>();
+        let mut cursor = Cursor::new(&buf);
+
+        let vr4s = VR4s::from_buffer(&mut cursor).unwrap();
 
-        return Ok(VR4s(vr4s));
+        assert_eq!(vr4s.0.len(), 2);
+        assert_eq!(vr4s[1][0], VR4s::BLOCK_SIZE as u8);
+        assert_eq!(cursor.position(), (VR4s::BLOCK_SIZE * 2) as u64);
     }
 }
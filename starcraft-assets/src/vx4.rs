@@ -1,10 +1,10 @@
 use super::errors::*;
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{ByteOrder, LittleEndian};
 use std::io::Cursor;
-use std::mem::MaybeUninit;
+use std::mem::size_of;
 use std::ops::{Index, IndexMut};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct VX4 {
     value: u16,
 }
@@ -40,26 +40,24 @@ impl VX4s {
     const BLOCK_SIZE: usize = 16;
 
     pub fn from_buffer(cursor: &mut Cursor<&Vec<u8>>) -> Result<VX4s> {
-        let buf_size = cursor.get_ref().len();
-        let out_size = buf_size / (VX4s::BLOCK_SIZE * std::mem::size_of::<u16>());
-
-        let mut vx4s: Vec<[VX4; VX4s::BLOCK_SIZE]> = Vec::with_capacity(out_size);
-        vx4s.resize(out_size, unsafe { MaybeUninit::uninit().assume_init() });
+        let block_bytes = VX4s::BLOCK_SIZE * size_of::<u16>();
+        let position = cursor.position() as usize;
+        let buf = cursor
+            .get_ref()
+            .get(position..)
+            .ok_or_else(|| format!("failed to read vx4s at position: '{}'", position))?;
 
-        let mut out_bytes: [u16; VX4s::BLOCK_SIZE] = unsafe { MaybeUninit::uninit().assume_init() };
-        for i in 0..out_size {
-            let previous_position = cursor.position();
-            cursor
-                .read_u16_into::<LittleEndian>(&mut out_bytes)
-                .chain_err(|| {
-                    format!("failed to read vx4s at position: '{}'", previous_position)
-                })?;
-            for j in 0..VX4s::BLOCK_SIZE {
-                vx4s[i][j] = VX4 {
-                    value: out_bytes[j],
+        let mut vx4s: Vec<[VX4; VX4s::BLOCK_SIZE]> = Vec::with_capacity(buf.len() / block_bytes);
+        for block in buf.chunks_exact(block_bytes) {
+            let mut vx4 = [VX4::default(); VX4s::BLOCK_SIZE];
+            for (j, minitile) in block.chunks_exact(size_of::<u16>()).enumerate() {
+                vx4[j] = VX4 {
+                    value: LittleEndian::read_u16(minitile),
                 };
             }
+            vx4s.push(vx4);
         }
+        cursor.set_position((position + vx4s.len() * block_bytes) as u64);
 
         Ok(VX4s(vx4s))
     }
@@ -0,0 +1,219 @@
+use super::errors::*;
+use byteorder::{ByteOrder, LittleEndian};
+use std::io::{Cursor, Write};
+
+/// Decoded audio: interleaved 16-bit samples, one per channel per frame.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Wav {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub samples: Vec<i16>,
+}
+
+impl Wav {
+    const RIFF: &'static [u8; 4] = b"RIFF";
+    const WAVE: &'static [u8; 4] = b"WAVE";
+    const FMT: &'static [u8; 4] = b"fmt ";
+    const DATA: &'static [u8; 4] = b"data";
+    const PCM: u16 = 1;
+    const FMT_SIZE: usize = 16;
+
+    /// Reads a RIFF WAVE file of 8 or 16-bit PCM.
+    ///
+    /// Sounds stored in an MPQ with ADPCM compression are decompressed to PCM by the
+    /// archive, so `MPQArchive::read_file` returns a file this can read. The archive
+    /// cannot yet undo the Huffman types 6 to 8 that Storm layers over ADPCM, so
+    /// compressed sounds fail in `read_file` until those tables are added.
+    pub fn from_buffer(cursor: &mut Cursor<&Vec<u8>>) -> Result<Wav> {
+        let start = cursor.position() as usize;
+        let buf = cursor
+            .get_ref()
+            .get(start..)
+            .filter(|buf| buf.len() >= 12)
+            .ok_or_else(|| format!("failed to read wav header at position: '{}'", start))?;
+        if &buf[0..4] != Wav::RIFF || &buf[8..12] != Wav::WAVE {
+            return Err("not a RIFF WAVE file".into());
+        }
+        let riff_end = (8 + LittleEndian::read_u32(&buf[4..]) as usize).min(buf.len());
+
+        let mut format = None;
+        let mut data = None;
+        let mut position = 12;
+        while position + 8 <= riff_end {
+            let id = &buf[position..position + 4];
+            let size = LittleEndian::read_u32(&buf[position + 4..]) as usize;
+            let chunk = buf
+                .get(position + 8..position + 8 + size)
+                .ok_or_else(|| format!("wav chunk at {} is cut off", start + position))?;
+            if id == Wav::FMT {
+                format = Some(chunk);
+            } else if id == Wav::DATA {
+                data = Some(chunk);
+            }
+            // chunks are padded to an even length
+            position += 8 + size + size % 2;
+        }
+        let format = format
+            .filter(|format| format.len() >= Wav::FMT_SIZE)
+            .ok_or("wav has no format chunk")?;
+        let data = data.ok_or("wav has no data chunk")?;
+
+        let format_tag = LittleEndian::read_u16(format);
+        let channels = LittleEndian::read_u16(&format[2..]);
+        let sample_rate = LittleEndian::read_u32(&format[4..]);
+        let bits_per_sample = LittleEndian::read_u16(&format[14..]);
+        if format_tag != Wav::PCM || channels == 0 {
+            return Err(format!(
+                "unsupported wav format {} with {} channels",
+                format_tag, channels
+            )
+            .into());
+        }
+        let samples = match bits_per_sample {
+            // 8-bit samples are unsigned
+            8 => data
+                .iter()
+                .map(|sample| ((*sample as i16) - 0x80) << 8)
+                .collect(),
+            16 => data.chunks_exact(2).map(LittleEndian::read_i16).collect(),
+            bits => return Err(format!("unsupported wav with {} bits per sample", bits).into()),
+        };
+        cursor.set_position((start + riff_end) as u64);
+
+        Ok(Wav {
+            sample_rate,
+            channels,
+            samples,
+        })
+    }
+
+    /// Samples per channel
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+
+    /// Length in seconds
+    pub fn duration(&self) -> f64 {
+        self.frames() as f64 / self.sample_rate.max(1) as f64
+    }
+
+    /// The samples of channel `channel`
+    pub fn channel(&self, channel: u16) -> impl Iterator<Item = i16> + '_ {
+        self.samples
+            .iter()
+            .skip(channel as usize)
+            .step_by(self.channels.max(1) as usize)
+            .copied()
+    }
+
+    /// The sound at `sample_rate`, interpolating linearly between samples
+    pub fn resample(&self, sample_rate: u32) -> Wav {
+        let frames = self.frames();
+        if sample_rate == self.sample_rate || frames == 0 || self.sample_rate == 0 {
+            return Wav {
+                sample_rate,
+                ..self.clone()
+            };
+        }
+
+        let channels = self.channels as usize;
+        let ratio = self.sample_rate as f64 / sample_rate as f64;
+        let resampled_frames = (frames as f64 / ratio).ceil() as usize;
+        let mut samples = Vec::with_capacity(resampled_frames * channels);
+        for frame in 0..resampled_frames {
+            let position = frame as f64 * ratio;
+            let before = (position as usize).min(frames - 1);
+            let after = (before + 1).min(frames - 1);
+            let weight = position - before as f64;
+            for channel in 0..channels {
+                let a = self.samples[before * channels + channel] as f64;
+                let b = self.samples[after * channels + channel] as f64;
+                samples.push((a + (b - a) * weight).round() as i16);
+            }
+        }
+
+        Wav {
+            sample_rate,
+            channels: self.channels,
+            samples,
+        }
+    }
+
+    /// Writes the sound as a 16-bit PCM RIFF WAVE file
+    pub fn write(&self, writer: &mut impl Write) -> Result<()> {
+        let data_size = self.samples.len() * 2;
+        let block_align = self.channels as u32 * 2;
+
+        let mut buf = Vec::with_capacity(44 + data_size);
+        buf.extend_from_slice(Wav::RIFF);
+        buf.extend_from_slice(&(36 + data_size as u32).to_le_bytes());
+        buf.extend_from_slice(Wav::WAVE);
+        buf.extend_from_slice(Wav::FMT);
+        buf.extend_from_slice(&(Wav::FMT_SIZE as u32).to_le_bytes());
+        buf.extend_from_slice(&Wav::PCM.to_le_bytes());
+        buf.extend_from_slice(&self.channels.to_le_bytes());
+        buf.extend_from_slice(&self.sample_rate.to_le_bytes());
+        buf.extend_from_slice(&(self.sample_rate * block_align).to_le_bytes());
+        buf.extend_from_slice(&(block_align as u16).to_le_bytes());
+        buf.extend_from_slice(&16u16.to_le_bytes());
+        buf.extend_from_slice(Wav::DATA);
+        buf.extend_from_slice(&(data_size as u32).to_le_bytes());
+        for sample in &self.samples {
+            buf.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        writer.write_all(&buf).chain_err(|| "failed to write wav")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wav_decodes_pcm_and_resamples() {
+        let stereo = Wav {
+            sample_rate: 22050,
+            channels: 2,
+            samples: vec![0, 100, 1000, -100, -32768, 32767],
+        };
+        let mut buf = Vec::new();
+        stereo.write(&mut buf).unwrap();
+        let mut cursor = Cursor::new(&buf);
+        assert_eq!(Wav::from_buffer(&mut cursor).unwrap(), stereo);
+        assert_eq!(cursor.position(), buf.len() as u64);
+        assert_eq!(stereo.frames(), 3);
+        assert_eq!(
+            stereo.channel(1).collect::<Vec<_>>(),
+            vec![100, -100, 32767]
+        );
+
+        // 8-bit mono, with a LIST chunk of odd length before the data
+        let mut buf = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+        buf.extend_from_slice(&16u32.to_le_bytes());
+        buf.extend_from_slice(&[1, 0, 1, 0]);
+        buf.extend_from_slice(&11025u32.to_le_bytes());
+        buf.extend_from_slice(&11025u32.to_le_bytes());
+        buf.extend_from_slice(&[1, 0, 8, 0]);
+        buf.extend_from_slice(b"LIST\x03\0\0\0abc\0");
+        buf.extend_from_slice(b"data\x03\0\0\0");
+        buf.extend_from_slice(&[0x80, 0xFF, 0x00]);
+        let size = (buf.len() - 8) as u32;
+        buf[4..8].copy_from_slice(&size.to_le_bytes());
+        let mono = Wav::from_buffer(&mut Cursor::new(&buf)).unwrap();
+        assert_eq!(mono.samples, vec![0, 127 << 8, -32768]);
+        assert_eq!(mono.duration(), 3.0 / 11025.0);
+
+        let upsampled = Wav {
+            sample_rate: 11025,
+            channels: 1,
+            samples: vec![0, 100, 200],
+        }
+        .resample(22050);
+        assert_eq!(upsampled.sample_rate, 22050);
+        assert_eq!(upsampled.samples, vec![0, 50, 100, 150, 200, 200]);
+
+        buf.truncate(40);
+        assert!(Wav::from_buffer(&mut Cursor::new(&buf)).is_err());
+    }
+}
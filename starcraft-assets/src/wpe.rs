@@ -1,10 +1,9 @@
 use super::errors::*;
-use std::io::{Cursor, Read};
-use std::mem::MaybeUninit;
+use std::io::Cursor;
 use std::ops::{Index, IndexMut};
 
 /// 256-color RGB Palette.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct WPE(pub [u8; WPEs::BLOCK_SIZE]);
 
 #[derive(Debug, Clone)]
@@ -28,22 +27,19 @@ impl WPEs {
     const BLOCK_SIZE: usize = 3;
 
     pub fn from_buffer(cursor: &mut Cursor<&Vec<u8>>) -> Result<WPEs> {
-        let buf_size = cursor.get_ref().len();
-        let out_size = buf_size / (WPEs::BLOCK_SIZE + 1);
-
-        let mut colors = Vec::with_capacity(out_size);
-        colors.resize(out_size, unsafe { MaybeUninit::uninit().assume_init() });
-
-        let mut out_bytes: [u8; WPEs::BLOCK_SIZE] = unsafe { MaybeUninit::uninit().assume_init() };
-        for i in 0..out_size {
-            let previous_position = cursor.position();
-            cursor
-                .read(&mut out_bytes)
-                .chain_err(|| format!("failed to read wpe at position: '{}'", previous_position))?;
-            cursor.set_position(cursor.position() + 1);
-            colors[i] = WPE([out_bytes[0], out_bytes[1], out_bytes[2]])
+        let position = cursor.position() as usize;
+        let buf = cursor
+            .get_ref()
+            .get(position..)
+            .ok_or_else(|| format!("failed to read wpe at position: '{}'", position))?;
+
+        // each entry is padded with an unused fourth byte
+        let mut colors = Vec::with_capacity(buf.len() / (WPEs::BLOCK_SIZE + 1));
+        for entry in buf.chunks_exact(WPEs::BLOCK_SIZE + 1) {
+            colors.push(WPE([entry[0], entry[1], entry[2]]));
         }
+        cursor.set_position((position + colors.len() * (WPEs::BLOCK_SIZE + 1)) as u64);
 
-        return Ok(WPEs(colors));
+        Ok(WPEs(colors))
     }
 }